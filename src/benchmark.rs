@@ -1,64 +1,314 @@
-const W_DAY: usize = 10;
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::day_16::Encoding;
+
+const W_DAY: usize = 14;
 const W_PART: usize = 10;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Table,
+    Tsv,
+}
+
+// One line of `--format tsv` output
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartResult {
+    pub day: u8,
+    pub variant: Option<String>,
+    pub part: u8,
+    pub answer: String,
+    pub seconds: f64,
+}
+
+// A part taking the raw input and the configured encoding, and returning its answer as text.
+// Parts of days that read text fail on input that isn't UTF-8.
+pub type Part = fn(&[u8], Encoding) -> Result<String, &'static str>;
+
+// A registered day, or another implementation of it such as `day_18::tree`, which is
+// benchmarked as a variant of the day
+pub struct Solver {
+    pub day: u8,
+    pub variant: Option<&'static str>,
+    pub part_1: Part,
+    pub part_2: Part,
+}
+
+impl Solver {
+    // `day 18`, or `day 18 tree` for a variant
+    fn label(&self) -> String {
+        match self.variant {
+            Some(variant) => format!("day {:02} {}", self.day, variant),
+            None => format!("day {:02}", self.day),
+        }
+    }
+
+    // The first column of `--format tsv` output: `18`, or `18 tree` for a variant
+    fn key(&self) -> String {
+        match self.variant {
+            Some(variant) => format!("{} {}", self.day, variant),
+            None => self.day.to_string(),
+        }
+    }
+}
+
+// What the parts of a day take: text for most days, bytes or a transmission in the configured
+// encoding for those that also read binary input
+pub trait SolverInput<'a>: Sized {
+    fn from_input(bytes: &'a [u8], encoding: Encoding) -> Result<Self, &'static str>;
+}
+
+impl<'a> SolverInput<'a> for &'a str {
+    fn from_input(bytes: &'a [u8], _encoding: Encoding) -> Result<Self, &'static str> {
+        std::str::from_utf8(bytes).map_err(|_| "not UTF-8")
+    }
+}
+
+impl<'a> SolverInput<'a> for &'a [u8] {
+    fn from_input(bytes: &'a [u8], _encoding: Encoding) -> Result<Self, &'static str> {
+        Ok(bytes)
+    }
+}
+
 fn mean(numbers: &[f64]) -> f64 {
     numbers.iter().sum::<f64>() / numbers.len() as f64
 }
 
-fn measure_run<S: ?Sized, T, F: Fn(&S) -> T>(f: &F, input: &S) -> f64 {
+pub fn measure_run<S: ?Sized, T, F: Fn(&S) -> T>(f: &F, input: &S) -> (T, f64) {
     let start = std::time::SystemTime::now();
-    _ = f(input);
+    let answer = f(input);
     let duration = start.elapsed().unwrap();
-    duration.as_secs_f64()
+    (answer, duration.as_secs_f64())
 }
 
-pub fn benchmark_run<S: ?Sized, T, F: Fn(&S) -> T>(f: F, input: &S) -> f64 {
-    let first_run = measure_run(&f, input);
-    let n = (1. / first_run) as i32;
+// Repeats the run for about `budget` seconds and returns the mean duration in seconds
+fn repeat_run<S: ?Sized, T, F: Fn(&S) -> T>(f: &F, input: &S, first_run: f64, budget: f64) -> f64 {
+    let n = (budget / first_run) as i32;
     if n <= 1 || first_run < 0.000001 {
         return first_run;
     }
     let mut run_times = vec![];
     for _ in 0..n {
-        run_times.push(measure_run(&f, input));
+        run_times.push(measure_run(f, input).1);
     }
     mean(&run_times)
 }
 
-pub fn print_header() {
+// Returns the answer of the first run and the mean duration in seconds
+pub fn benchmark_run<S: ?Sized, T, F: Fn(&S) -> T>(f: F, input: &S, budget: f64) -> (T, f64) {
+    let (answer, first_run) = measure_run(&f, input);
+    (answer, repeat_run(&f, input, first_run, budget))
+}
+
+// Like `benchmark_run`, but gives up when the first run exceeds the configured timeout or
+// the part can't read the input
+fn benchmark_part(f: Part, input: &[u8], config: &Config) -> Result<(String, f64), &'static str> {
+    let run = |input: &[u8]| f(input, config.encoding);
+    let Some(timeout) = config.timeout else {
+        let (answer, seconds) = benchmark_run(run, input, config.budget);
+        return Ok((answer?, seconds));
+    };
+
+    let (answer, first_run) = run_with_timeout(f, input, config.encoding, timeout)?;
+    Ok((answer?, repeat_run(&run, input, first_run, config.budget)))
+}
+
+// Runs the part once in its own thread and gives up after `timeout` seconds. The timed out
+// run is left behind in its thread, and a run that panics is reported as failed.
+pub fn run_with_timeout(f: Part, input: &[u8], encoding: Encoding, timeout: f64) -> Result<(Result<String, &'static str>, f64), &'static str> {
+    let (sender, receiver) = mpsc::channel();
+    let thread_input: Vec<u8> = input.to_vec();
+    std::thread::spawn(move || {
+        _ = sender.send(measure_run(&|input: &[u8]| f(input, encoding), thread_input.as_slice()));
+    });
+
+    match receiver.recv_timeout(Duration::from_secs_f64(timeout)) {
+        Ok(run) => Ok(run),
+        Err(RecvTimeoutError::Timeout) => Err("timeout"),
+        Err(RecvTimeoutError::Disconnected) => Err("failed"),
+    }
+}
+
+pub fn print_header(with_baseline: bool) {
+    let columns: usize = if with_baseline { 4 } else { 2 };
+
     print!("{:<w$}", "day", w = W_DAY);
-    print!("{:<w$}", "part 1", w = W_PART);
-    print!("{:<w$}", "part 2", w = W_PART);
+    for part in 1..=2 {
+        print!("{:<w$}", format!("part {}", part), w = W_PART);
+        if with_baseline {
+            print!("{:<w$}", "baseline", w = W_PART);
+        }
+    }
+    println!();
+    println!("{:-<w$}", "", w = W_DAY + W_PART * columns);
+}
+
+fn format_duration(result: &Result<(String, f64), &str>) -> String {
+    match result {
+        Ok((_, seconds)) => {
+            let duration = format!("{:.3}", seconds * 1000.).to_string();
+            format!("{} ms", &duration[..5])
+        }
+        Err(e) => e.to_string(),
+    }
+}
+
+fn format_change(result: &Result<(String, f64), &str>, baseline: Option<f64>) -> String {
+    match (result, baseline) {
+        (Ok((_, seconds)), Some(b)) => format!("{:+.1}%", (seconds / b - 1.) * 100.),
+        _ => "-".to_string(),
+    }
+}
+
+pub fn print_day(solver: &Solver, parts: &[Result<(String, f64), &str>; 2], baseline: Option<&[PartResult]>) {
+    print!("{:<w$}", solver.label(), w = W_DAY);
+
+    for (part, result) in (1..=2).zip(parts) {
+        print!("{:<w$}", format_duration(result), w = W_PART);
+
+        if let Some(baseline) = baseline {
+            let base = baseline
+                .iter()
+                .find(|r| r.day == solver.day && r.variant.as_deref() == solver.variant && r.part == part)
+                .map(|r| r.seconds);
+            print!("{:<w$}", format_change(result, base), w = W_PART);
+        }
+    }
     println!();
-    println!("{:-<w$}", "", w = W_DAY + W_PART * 2);
 }
 
-pub fn print_day(day: u8, p1: f64, p2: f64) {
-    print!("{:<w$}", format!("day {:02}", day), w = W_DAY);
+pub fn print_day_tsv(solver: &Solver, parts: &[Result<(String, f64), &str>; 2]) {
+    for (part, result) in (1..=2).zip(parts) {
+        match result {
+            Ok((answer, seconds)) => println!("{}\t{}\t{}\t{}", solver.key(), part, answer, seconds),
+            Err(e) => eprintln!("{} part {}: {}", solver.label(), part, e),
+        }
+    }
+}
 
-    let mut p1_dur = format!("{:.3}", p1 * 1000.).to_string();
-    p1_dur = format!("{} ms", &p1_dur[..5]);
-    print!("{:<w$}", p1_dur, w = W_PART);
+// Parses the output of `--format tsv`
+pub fn parse_tsv(tsv: &str) -> Vec<PartResult> {
+    tsv.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 4 {
+                return None;
+            }
+            let (day, variant): (&str, Option<String>) = match fields[0].split_once(' ') {
+                Some((day, variant)) => (day, Some(variant.to_string())),
+                None => (fields[0], None),
+            };
+            Some(PartResult {
+                day: day.parse().ok()?,
+                variant,
+                part: fields[1].parse().ok()?,
+                answer: fields[2].to_string(),
+                seconds: fields[3].parse().ok()?,
+            })
+        })
+        .collect()
+}
 
-    let mut p2_dur = format!("{:.3}", p2 * 1000.).to_string();
-    p2_dur = format!("{} ms", &p2_dur[..5]);
-    println!("{:<w$}", p2_dur, w = W_PART);
+pub fn read_input(inputs: &Path, day: u8) -> Result<String, String> {
+    let input_path = inputs.join(format!("{:02}.in", day));
+    std::fs::read_to_string(&input_path).map_err(|e| format!("Unable to open input file {}: {}", input_path.display(), e))
 }
 
+// For inputs that aren't necessarily text
+pub fn read_input_bytes(inputs: &Path, day: u8) -> Result<Vec<u8>, String> {
+    let input_path = inputs.join(format!("{:02}.in", day));
+    std::fs::read(&input_path).map_err(|e| format!("Unable to open input file {}: {}", input_path.display(), e))
+}
+
+pub fn benchmark_all(solvers: &[Solver], config: &Config) {
+    let baseline: Option<Vec<PartResult>> = config.baseline.as_ref().and_then(|path| {
+        match std::fs::read_to_string(path) {
+            Ok(tsv) => Some(parse_tsv(&tsv)),
+            Err(e) => {
+                eprintln!("Unable to open baseline file {}: {}", path.display(), e);
+                None
+            }
+        }
+    });
+
+    if config.format == Format::Table {
+        print_header(baseline.is_some());
+    }
+
+    for solver in solvers.iter().filter(|s| config.days.is_empty() || config.days.contains(&s.day)) {
+        let raw_input = match read_input_bytes(&config.inputs, solver.day) {
+            Ok(raw_input) => raw_input,
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        };
+
+        let parts = [
+            benchmark_part(solver.part_1, &raw_input, config),
+            benchmark_part(solver.part_2, &raw_input, config),
+        ];
+
+        match config.format {
+            Format::Table => print_day(solver, &parts, baseline.as_deref()),
+            Format::Tsv => print_day_tsv(solver, &parts),
+        }
+    }
+}
+
+// `solvers!(day_16, day_18, day_18::tree)` registers the parts of `day_16` and `day_18`, and
+// those of `day_18::tree` as the `tree` variant of day 18
 #[macro_export]
-macro_rules! benchmark_all {
-    ($($day:ident),*) => {{
-        print_header();
-        $(
-        let day_number_string = &stringify!($day).to_string()[4..];
-        let input_path = format!("inputs/{}.in", day_number_string);
-        let error_msg = format!("Unable to open input file {}", &input_path);
-        let raw_input = std::fs::read_to_string(&input_path).expect(error_msg.as_str());
-
-        let p1_duration = benchmark_run($day::part_1, &raw_input);
-        let p2_duration = benchmark_run($day::part_2, &raw_input);
-
-        print_day(day_number_string.parse().unwrap(), p1_duration, p2_duration);
-        )*
-    }};
-}
\ No newline at end of file
+macro_rules! solvers {
+    ($($day:ident $(:: $variant:ident)?),*) => {
+        vec![$(
+            $crate::benchmark::Solver {
+                day: stringify!($day)[4..].parse().unwrap(),
+                variant: None $(.or(Some(stringify!($variant))))?,
+                part_1: |input, encoding| Ok($day $(::$variant)?::part_1($crate::benchmark::SolverInput::from_input(input, encoding)?).to_string()),
+                part_2: |input, encoding| Ok($day $(::$variant)?::part_2($crate::benchmark::SolverInput::from_input(input, encoding)?).to_string()),
+            },
+        )*]
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day_18;
+
+    #[test]
+    fn parse_tsv_works() {
+        let result = parse_tsv("16\t1\t981\t0.000047\n16\t2\t299227024091\t0.000039\nday 16 part 2: timeout\n18 tree\t1\t4140\t0.0012\n");
+
+        assert_eq!(result, vec![
+            PartResult { day: 16, variant: None, part: 1, answer: "981".to_string(), seconds: 0.000047 },
+            PartResult { day: 16, variant: None, part: 2, answer: "299227024091".to_string(), seconds: 0.000039 },
+            PartResult { day: 18, variant: Some("tree".to_string()), part: 1, answer: "4140".to_string(), seconds: 0.0012 },
+        ]);
+    }
+
+    #[test]
+    fn variants_are_registered() {
+        let solvers: Vec<Solver> = crate::solvers!(day_18, day_18::tree);
+
+        assert_eq!(solvers.iter().map(Solver::label).collect::<Vec<String>>(), vec!["day 18", "day 18 tree"]);
+        assert_eq!(solvers[1].key(), "18 tree");
+        assert_eq!((solvers[1].part_1)(b"[1,2]\n[3,4]", Encoding::Hex), Ok("55".to_string()));
+    }
+
+    #[test]
+    fn benchmark_part_times_out() {
+        let config = Config { timeout: Some(0.05), ..Config::default() };
+        let slow = |_: &[u8], _: Encoding| -> Result<String, &'static str> {
+            std::thread::sleep(Duration::from_secs(1));
+            Ok(String::new())
+        };
+
+        assert_eq!(benchmark_part(slow, b"", &config), Err("timeout"));
+        assert_eq!(benchmark_part(|s, _| Ok(String::from_utf8_lossy(s).to_uppercase()), b"abc", &config).unwrap().0, "ABC");
+    }
+}
@@ -0,0 +1,241 @@
+use std::path::PathBuf;
+
+use crate::benchmark::Format;
+use crate::config::Config;
+
+pub const USAGE: &str = "usage: rust [watch | serve [--port P] | compile EXPR] [--day N]... [--format table|tsv] [--visualize [--no-color] [--fps F]]
+            [--emit FORMAT [--strict]] [--encoding hex|binary|base64|auto]
+            [--inputs DIR] [--budget SECONDS] [--timeout SECONDS] [--baseline FILE]";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Benchmark,
+    Watch,
+    Visualize,
+    Serve,
+    Emit,
+    Compile,
+}
+
+#[derive(Debug)]
+pub struct Args {
+    pub command: Command,
+    pub config: Config,
+    pub color: bool,
+    pub fps: f64,
+    pub port: u16,
+    pub emit: String,
+    pub strict: bool,
+    pub expression: String,
+}
+
+// Flags override the settings from `config`
+pub fn parse_args(args: &[String], config: Config) -> Result<Args, String> {
+    let mut parsed: Args = Args {
+        command: Command::Benchmark,
+        config,
+        color: std::env::var_os("NO_COLOR").is_none(),
+        fps: 10.,
+        port: 8080,
+        emit: String::new(),
+        strict: false,
+        expression: String::new(),
+    };
+
+    let mut flags = args.iter().peekable();
+    match flags.peek().map(|a| a.as_str()) {
+        Some("watch") => parsed.command = Command::Watch,
+        Some("serve") => parsed.command = Command::Serve,
+        Some("compile") => parsed.command = Command::Compile,
+        _ => {}
+    }
+    if parsed.command != Command::Benchmark {
+        flags.next();
+    }
+    if parsed.command == Command::Compile {
+        match flags.next() {
+            Some(expression) => parsed.expression = expression.to_string(),
+            None => return Err("compile needs an expression".to_string()),
+        }
+    }
+
+    let mut days: Vec<u8> = vec![];
+
+    while let Some(flag) = flags.next() {
+        match flag.as_str() {
+            "--visualize" => parsed.command = Command::Visualize,
+            "--no-color" => parsed.color = false,
+            "--strict" => parsed.strict = true,
+            _ => {
+                let value: &str = match flags.next() {
+                    Some(value) => value,
+                    None => return Err(format!("Missing value for {}", flag)),
+                };
+
+                match flag.as_str() {
+                    "--day" => days.push(parse_day(value)?),
+                    "--format" => parsed.config.format = parse_format(value)?,
+                    "--inputs" => parsed.config.inputs = PathBuf::from(value),
+                    "--budget" => parsed.config.budget = parse_seconds(value)?,
+                    "--timeout" => parsed.config.timeout = Some(parse_seconds(value)?),
+                    "--baseline" => parsed.config.baseline = Some(PathBuf::from(value)),
+                    "--fps" => parsed.fps = parse_fps(value)?,
+                    "--emit" => {
+                        parsed.command = Command::Emit;
+                        parsed.emit = value.to_string();
+                    }
+                    "--encoding" => parsed.config.encoding = value.parse()?,
+                    "--port" => parsed.port = value.parse().map_err(|_| format!("Invalid port {}", value))?,
+                    _ => return Err(format!("Unknown argument {}", flag)),
+                }
+            }
+        }
+    }
+
+    if !days.is_empty() {
+        parsed.config.days = days;
+    }
+
+    match parsed.command {
+        Command::Watch if parsed.config.days.len() != 1 => return Err("watch needs exactly one --day".to_string()),
+        Command::Visualize if parsed.config.days.len() != 1 => return Err("--visualize needs exactly one --day".to_string()),
+        Command::Emit if parsed.config.days.len() != 1 => return Err("--emit needs exactly one --day".to_string()),
+        _ => {}
+    }
+
+    Ok(parsed)
+}
+
+pub fn parse_day(value: &str) -> Result<u8, String> {
+    match value.parse::<u8>() {
+        Ok(day) if (1..=25).contains(&day) => Ok(day),
+        _ => Err(format!("Invalid day {}", value)),
+    }
+}
+
+pub fn parse_seconds(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(seconds) if seconds > 0. => Ok(seconds),
+        _ => Err(format!("Invalid number of seconds {}", value)),
+    }
+}
+
+fn parse_fps(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(fps) if fps > 0. => Ok(fps),
+        _ => Err(format!("Invalid frame rate {}", value)),
+    }
+}
+
+pub fn parse_format(value: &str) -> Result<Format, String> {
+    match value {
+        "table" => Ok(Format::Table),
+        "tsv" => Ok(Format::Tsv),
+        _ => Err(format!("Invalid format {}", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day_16::Encoding;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(|a| a.to_string()).collect()
+    }
+
+    fn parse_args_default(args: &[String]) -> Result<Args, String> {
+        parse_args(args, Config::default())
+    }
+
+    #[test]
+    fn no_arguments_benchmark_all_days() {
+        let result = parse_args_default(&args("")).unwrap();
+
+        assert_eq!(result.command, Command::Benchmark);
+        assert!(result.config.days.is_empty());
+        assert_eq!(result.config.format, Format::Table);
+    }
+
+    #[test]
+    fn watch_works() {
+        let result = parse_args_default(&args("watch --day 16 --format tsv")).unwrap();
+
+        assert_eq!(result.command, Command::Watch);
+        assert_eq!(result.config.days, vec![16]);
+        assert_eq!(result.config.format, Format::Tsv);
+    }
+
+    #[test]
+    fn visualize_works() {
+        let result = parse_args_default(&args("--day 20 --visualize --no-color --fps 2.5")).unwrap();
+
+        assert_eq!(result.command, Command::Visualize);
+        assert_eq!(result.config.days, vec![20]);
+        assert!(!result.color);
+        assert_eq!(result.fps, 2.5);
+    }
+
+    #[test]
+    fn serve_works() {
+        let result = parse_args_default(&args("serve --port 9000")).unwrap();
+
+        assert_eq!(result.command, Command::Serve);
+        assert_eq!(result.port, 9000);
+    }
+
+    #[test]
+    fn emit_works() {
+        let result = parse_args_default(&args("--emit hex --day 16")).unwrap();
+
+        assert_eq!(result.command, Command::Emit);
+        assert_eq!(result.emit, "hex");
+        assert!(!result.strict);
+
+        assert_eq!(result.config.encoding, Encoding::Hex);
+
+        let result = parse_args_default(&args("--day 16 --strict --emit disasm --encoding binary")).unwrap();
+
+        assert!(result.strict);
+        assert_eq!(result.config.encoding, Encoding::Binary);
+        assert!(parse_args_default(&args("--encoding base32")).is_err());
+    }
+
+    #[test]
+    fn compile_works() {
+        let result = parse_args_default(&["compile".to_string(), "sum(1, 2)".to_string()]).unwrap();
+
+        assert_eq!(result.command, Command::Compile);
+        assert_eq!(result.expression, "sum(1, 2)");
+    }
+
+    #[test]
+    fn flags_override_config() {
+        let config = Config::parse("days = 16, 18\nbudget = 2\ntimeout = 10").unwrap();
+
+        let result = parse_args(&args("--day 22 --budget 0.5 --inputs /tmp/aoc"), config.clone()).unwrap();
+
+        assert_eq!(result.config.days, vec![22]);
+        assert_eq!(result.config.budget, 0.5);
+        assert_eq!(result.config.timeout, Some(10.));
+        assert_eq!(result.config.inputs, PathBuf::from("/tmp/aoc"));
+
+        let result = parse_args(&args("--format tsv"), config).unwrap();
+
+        assert_eq!(result.config.days, vec![16, 18]);
+        assert_eq!(result.config.format, Format::Tsv);
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        assert!(parse_args_default(&args("watch")).is_err());
+        assert!(parse_args_default(&args("compile")).is_err());
+        assert!(parse_args_default(&args("--day 26")).is_err());
+        assert!(parse_args_default(&args("--day")).is_err());
+        assert!(parse_args_default(&args("--days 16")).is_err());
+        assert!(parse_args_default(&args("--visualize")).is_err());
+        assert!(parse_args_default(&args("--day 20 --visualize --fps 0")).is_err());
+        assert!(parse_args_default(&args("serve --port 65536")).is_err());
+        assert!(parse_args_default(&args("--timeout -1")).is_err());
+    }
+}
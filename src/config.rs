@@ -0,0 +1,132 @@
+use std::path::PathBuf;
+
+use crate::benchmark::Format;
+use crate::cli::{parse_day, parse_format, parse_seconds};
+use crate::day_16::Encoding;
+
+const CONFIG_FILE: &str = "aoc.conf";
+
+// Runner settings, read from `aoc.conf` and overridden by command-line flags
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub inputs: PathBuf,
+    pub format: Format,
+    pub budget: f64,
+    pub timeout: Option<f64>,
+    pub baseline: Option<PathBuf>,
+    // How the days that read binary input take it, for now only day 16
+    pub encoding: Encoding,
+    pub days: Vec<u8>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            inputs: PathBuf::from("inputs"),
+            format: Format::Table,
+            budget: 1.,
+            timeout: None,
+            baseline: None,
+            encoding: Encoding::Hex,
+            days: vec![],
+        }
+    }
+}
+
+impl Config {
+    // Reads `aoc.conf` from the current directory, falling back to the home directory
+    pub fn load() -> Result<Config, String> {
+        let mut candidates: Vec<PathBuf> = vec![PathBuf::from(CONFIG_FILE)];
+        if let Some(home) = std::env::var_os("HOME") {
+            candidates.push(PathBuf::from(home).join(CONFIG_FILE));
+        }
+
+        match candidates.iter().find(|path| path.is_file()) {
+            Some(path) => {
+                let contents: String = std::fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+                Config::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+            }
+            None => Ok(Config::default()),
+        }
+    }
+
+    // One `key = value` per line, `#` starts a comment
+    pub fn parse(contents: &str) -> Result<Config, String> {
+        let mut config: Config = Config::default();
+
+        for (line_id, line) in contents.lines().enumerate() {
+            let line: &str = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("line {}: expected key = value", line_id + 1));
+            };
+
+            config.set(key.trim(), value.trim()).map_err(|e| format!("line {}: {}", line_id + 1, e))?;
+        }
+
+        Ok(config)
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "inputs" => self.inputs = PathBuf::from(value),
+            "format" => self.format = parse_format(value)?,
+            "budget" => self.budget = parse_seconds(value)?,
+            "timeout" => self.timeout = Some(parse_seconds(value)?),
+            "baseline" => self.baseline = Some(PathBuf::from(value)),
+            "encoding" => self.encoding = value.parse()?,
+            "days" => {
+                self.days = value
+                    .split(',')
+                    .map(|day| parse_day(day.trim()))
+                    .collect::<Result<Vec<u8>, String>>()?;
+            }
+            _ => return Err(format!("Unknown key {}", key)),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_works() {
+        let result = Config::parse("
+# Benchmark settings
+inputs = /data/aoc/2021
+format = tsv
+budget = 0.5     # seconds per part
+timeout = 30
+baseline = bench_baseline.tsv
+encoding = auto
+days = 16, 18,22
+").unwrap();
+
+        assert_eq!(result, Config {
+            inputs: PathBuf::from("/data/aoc/2021"),
+            format: Format::Tsv,
+            budget: 0.5,
+            timeout: Some(30.),
+            baseline: Some(PathBuf::from("bench_baseline.tsv")),
+            encoding: Encoding::Auto,
+            days: vec![16, 18, 22],
+        });
+    }
+
+    #[test]
+    fn empty_file_gives_defaults() {
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn invalid_lines_are_rejected() {
+        assert_eq!(Config::parse("format = table\nbudget").unwrap_err(), "line 2: expected key = value");
+        assert_eq!(Config::parse("colour = red").unwrap_err(), "line 1: Unknown key colour");
+        assert_eq!(Config::parse("days = 16, 32").unwrap_err(), "line 1: Invalid day 32");
+    }
+}
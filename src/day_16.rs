@@ -1,184 +1,829 @@
-use std::cmp::{min, max};
+mod bits;
+mod disasm;
+mod encoder;
+mod export;
+mod expression;
+mod optimize;
+mod registry;
+mod stats;
+mod stream;
+mod trace;
+mod visit;
 
-pub fn part_1(input: &str) -> usize {
-    let bin_str: String = hex_to_binary(&input);
-    let packet: Packet = parse_packet(&bin_str).0;
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::benchmark::SolverInput;
+use bits::BitSource;
+use disasm::disassemble;
+use encoder::encode;
+use export::{to_dot, to_json};
+pub use expression::compile;
+use optimize::{optimize, Passes};
+pub use registry::{Arity, EvalFault, Registry};
+use stats::stats;
+use stream::PacketStream;
+use trace::trace;
+pub(crate) use visit::{walk, Visitor};
+
+// The puzzle takes the first transmission of the input, hex, base64 or binary
+pub fn part_1(input: Transmission) -> usize {
+    let options: DecodeOptions = DecodeOptions { encoding: input.encoding, ..DecodeOptions::default() };
+    let packet: Packet = first_packet(input.bytes, &options).unwrap_or_else(|e| panic!("{}", e));
     sum_versions(&packet)
 }
 
-pub fn part_2(input: &str) -> usize {
-    let bin_str: String = hex_to_binary(&input);
-    let packet: Packet = parse_packet(&bin_str).0;
+pub fn part_2(input: Transmission) -> u128 {
+    let options: DecodeOptions = DecodeOptions { encoding: input.encoding, ..DecodeOptions::default() };
+    evaluate_input(input.bytes, &options).unwrap_or_else(|e| panic!("{}", e))
+}
 
-    evaluate(&packet)
+// The puzzle input with the encoding it was given in, `--encoding` for the runner. `Auto` is
+// resolved before the parts see it, so ambiguous input fails like input that isn't UTF-8.
+#[derive(Debug, Clone, Copy)]
+pub struct Transmission<'a> {
+    pub bytes: &'a [u8],
+    pub encoding: Encoding,
 }
 
+impl<'a> Transmission<'a> {
+    #[cfg(test)]
+    fn hex(text: &'a str) -> Self {
+        Transmission { bytes: text.as_bytes(), encoding: Encoding::Hex }
+    }
+}
 
-#[derive(Debug)]
+impl<'a> SolverInput<'a> for Transmission<'a> {
+    fn from_input(bytes: &'a [u8], encoding: Encoding) -> Result<Self, &'static str> {
+        let encoding: Encoding = match encoding {
+            Encoding::Auto => Encoding::detect(bytes).map_err(|_| "both hex and base64, pick an encoding")?,
+            encoding => encoding,
+        };
+        Ok(Transmission { bytes, encoding })
+    }
+}
+
+// Decodes the first transmission of `input` and evaluates it with the operators of
+// `options.registry`
+pub fn evaluate_input(input: &[u8], options: &DecodeOptions) -> Result<u128, BitsError> {
+    let packet: Packet = first_packet(input, options)?;
+    evaluate(&packet, &options.registry)
+}
+
+// An empty input is a truncated transmission
+fn first_packet(input: &[u8], options: &DecodeOptions) -> Result<Packet, BitsError> {
+    PacketStream::new(input, options.clone()).next().unwrap_or(Err(BitsError::Truncated { offset: 0 }))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    #[default]
+    Hex,
+    Binary,
+    Base64,
+    // Hex if the input only has hex digits and whitespace, else base64 if it only has base64
+    // characters and whitespace, else binary. Hex digits are base64 characters too, so hex
+    // digits that also split into whole groups of 4 base64 characters are ambiguous.
+    Auto,
+}
+
+impl Encoding {
+    pub fn detect(input: &[u8]) -> Result<Encoding, BitsError> {
+        let hex: bool = input.iter().all(|b| b.is_ascii_hexdigit() || b.is_ascii_whitespace());
+        let base64: bool = input.iter().all(|&b| base64_digit(b).is_some() || b == b'=' || b.is_ascii_whitespace());
+        let groups: bool = input.split(u8::is_ascii_whitespace).all(|transmission| transmission.len() % 4 == 0);
+        let empty: bool = input.iter().all(u8::is_ascii_whitespace);
+
+        match (hex, base64) {
+            (true, _) if groups && !empty => Err(BitsError::AmbiguousEncoding),
+            (true, _) => Ok(Encoding::Hex),
+            (false, true) => Ok(Encoding::Base64),
+            (false, false) => Ok(Encoding::Binary),
+        }
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(Encoding::Hex),
+            "binary" => Ok(Encoding::Binary),
+            "base64" => Ok(Encoding::Base64),
+            "auto" => Ok(Encoding::Auto),
+            _ => Err(format!("Unknown encoding {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name: &str = match self {
+            Encoding::Hex => "hex",
+            Encoding::Binary => "binary",
+            Encoding::Base64 => "base64",
+            Encoding::Auto => "auto",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// The 6 bits of a character of the standard base64 alphabet
+fn base64_digit(byte: u8) -> Option<u8> {
+    match byte {
+        b'A'..=b'Z' => Some(byte - b'A'),
+        b'a'..=b'z' => Some(byte - b'a' + 26),
+        b'0'..=b'9' => Some(byte - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DecodeOptions {
+    // Reject characters other than hex or base64 digits and whitespace, and require zero padding
+    pub strict: bool,
+    pub encoding: Encoding,
+    // Arity of the operators when decoding, and their meaning when evaluating
+    pub registry: Arc<Registry>,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        DecodeOptions { strict: false, encoding: Encoding::default(), registry: Arc::clone(Registry::standard()) }
+    }
+}
+
+enum Output {
+    Hex,
+    Disasm,
+    Stats,
+    Optimized(Passes),
+    Json,
+    Dot,
+    Trace,
+}
+
+// Renders every transmission in the input in another format, one after another:
+//   hex               re-encoded
+//   disasm            indented listing with the bit offset and span of every packet
+//   stats             packet counts by type, the nesting depth and a version histogram
+//   optimized[:LIST]  shortest re-encoding after the optimization passes, all by default
+//   json              the packet tree with the header, length field and value of every packet
+//   dot               the same as a Graphviz digraph
+//   trace             every operator with its operand values, bottom-up, and the final value
+pub fn emit(input: &[u8], format: &str, options: &DecodeOptions) -> Result<String, String> {
+    let output: Output = match format.split_once(':') {
+        None if format == "hex" => Output::Hex,
+        None if format == "disasm" => Output::Disasm,
+        None if format == "stats" => Output::Stats,
+        None if format == "optimized" => Output::Optimized(Passes::all()),
+        Some(("optimized", passes)) => Output::Optimized(Passes::parse(passes)?),
+        None if format == "json" => Output::Json,
+        None if format == "dot" => Output::Dot,
+        None if format == "trace" => Output::Trace,
+        _ => return Err(format!("Unknown output format {}", format)),
+    };
+
+    let mut outputs: Vec<String> = vec![];
+    for (i, packet) in PacketStream::new(input, options.clone()).enumerate() {
+        let packet: Packet = packet.map_err(|e| format!("transmission {}: {}", i + 1, e))?;
+        outputs.push(match output {
+            Output::Hex => encode(&packet).map_err(|e| e.to_string())?,
+            Output::Disasm => disassemble(&packet),
+            Output::Stats => stats(&packet).report(),
+            Output::Optimized(passes) => encode(&optimize(packet, &passes, &options.registry)).map_err(|e| e.to_string())?,
+            Output::Json => to_json(&packet, &options.registry),
+            Output::Dot => to_dot(&packet, &options.registry),
+            Output::Trace => trace(&packet, &options.registry),
+        });
+    }
+    Ok(outputs.join("\n"))
+}
+
+// Decodes the first hex transmission of `input`, skipping anything that isn't a hex digit or
+// whitespace and ignoring the bits after the outermost packet
+#[cfg(test)]
+fn decode(input: &str) -> Result<Packet, BitsError> {
+    first_packet(input.as_bytes(), &DecodeOptions::default())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BitsError {
+    // A sub-packet count or length that doesn't fit into the 11 or 15 bit length field
+    LengthOverflow { length_type_id: bool, length: usize },
+    // An operator with a number of sub-packets it can't be applied to
+    Arity { operator: &'static str, arity: Arity, operands: usize, offset: usize },
+    // A literal or an evaluated value that doesn't fit into 128 bits
+    Overflow { offset: usize },
+    // A custom operator without a value for its operands
+    Undefined { offset: usize },
+    // Strict decoding only: a character that isn't a hex or base64 digit or whitespace, counted in bytes from 0
+    InvalidCharacter { character: char, position: usize },
+    // The transmission ends inside the packet at `offset`
+    Truncated { offset: usize },
+    // Sub-packets of a length type 0 operator that don't add up to its length field
+    LengthMismatch { offset: usize, length: usize, consumed: usize },
+    // Strict decoding only: a set bit after the outermost packet
+    NonZeroPadding { position: usize },
+    // `Encoding::Auto` only: text that is valid hex and valid base64
+    AmbiguousEncoding,
+    Io(String),
+}
+
+impl fmt::Display for BitsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BitsError::LengthOverflow { length_type_id: true, length } => {
+                write!(f, "{} sub-packets don't fit into an 11 bit length field", length)
+            }
+            BitsError::LengthOverflow { length_type_id: false, length } => {
+                write!(f, "{} bits of sub-packets don't fit into a 15 bit length field", length)
+            }
+            BitsError::Arity { operator, arity: arity @ Arity::Exactly(_), operands, offset } => {
+                write!(f, "{} at bit {} needs {}, got {}", operator, offset, arity, operands)
+            }
+            BitsError::Arity { operator, arity, offset, .. } => write!(f, "{} at bit {} needs {}", operator, offset, arity),
+            BitsError::Overflow { offset } => write!(f, "value of the packet at bit {} overflows 128 bits", offset),
+            BitsError::Undefined { offset } => write!(f, "value of the packet at bit {} is undefined", offset),
+            BitsError::InvalidCharacter { character, position } => {
+                write!(f, "invalid character {:?} at position {}", character, position)
+            }
+            BitsError::Truncated { offset } => write!(f, "packet at bit {} is truncated", offset),
+            BitsError::LengthMismatch { offset, length, consumed } => {
+                write!(f, "operator at bit {} has a length of {} bits, but its sub-packets take {}", offset, length, consumed)
+            }
+            BitsError::NonZeroPadding { position } => write!(f, "non-zero padding at bit {}", position),
+            BitsError::AmbiguousEncoding => write!(f, "transmission is both valid hex and base64, pick an encoding"),
+            BitsError::Io(e) => write!(f, "unable to read transmission: {}", e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Sum,
+    Product,
+    Min,
+    Max,
+    Gt,
+    Lt,
+    Eq,
+}
+
+impl Operator {
+    const ALL: [Operator; 7] = [Operator::Sum, Operator::Product, Operator::Min, Operator::Max, Operator::Gt, Operator::Lt, Operator::Eq];
+
+    // Type ID 4 is a literal, every other ID is an operator
+    fn from_type_id(type_id: u8) -> Option<Operator> {
+        Operator::ALL.into_iter().find(|o| o.type_id() == type_id)
+    }
+
+    fn type_id(&self) -> u8 {
+        match self {
+            Operator::Sum => 0,
+            Operator::Product => 1,
+            Operator::Min => 2,
+            Operator::Max => 3,
+            Operator::Gt => 5,
+            Operator::Lt => 6,
+            Operator::Eq => 7,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Operator::Sum => "sum",
+            Operator::Product => "product",
+            Operator::Min => "min",
+            Operator::Max => "max",
+            Operator::Gt => "gt",
+            Operator::Lt => "lt",
+            Operator::Eq => "eq",
+        }
+    }
+
+    fn is_comparison(&self) -> bool {
+        matches!(self, Operator::Gt | Operator::Lt | Operator::Eq)
+    }
+
+    // Comparisons take exactly two operands, everything else at least one
+    fn accepts(&self, operands: usize) -> bool {
+        match self.is_comparison() {
+            true => operands == 2,
+            false => operands >= 1,
+        }
+    }
+}
+
+// `offset` and `span` locate the packet in the transmission, in bits. They are ignored when
+// comparing packets.
+#[derive(Debug, Clone)]
 #[allow(dead_code)]
-struct LiteralPacket {
+pub(crate) struct LiteralPacket {
     version: u8,
     type_id: u8,
-    value: usize,
+    value: u128,
+    offset: usize,
+    span: usize,
 }
 
+// Comparing and dropping operators works without recursion, so trees of any depth are safe
 #[derive(Debug)]
 #[allow(dead_code)]
-struct OperatorPacket {
+pub(crate) struct OperatorPacket {
     version: u8,
-    type_id: u8,
+    operator: Operator,
+    // What the registry the packet was decoded with calls the operator, which differs from
+    // `operator.name()` where a custom operator took over the type ID
+    name: &'static str,
     length_type_id: bool,
-    length_field: u16,
+    length_field: usize,
     subpackets: Vec<Packet>,
+    offset: usize,
+    span: usize,
+}
+
+impl PartialEq for LiteralPacket {
+    fn eq(&self, other: &Self) -> bool {
+        (self.version, self.type_id, self.value) == (other.version, other.type_id, other.value)
+    }
+}
+
+impl Eq for LiteralPacket {}
+
+impl LiteralPacket {
+    pub(crate) fn value(&self) -> u128 {
+        self.value
+    }
+}
+
+impl OperatorPacket {
+    pub(crate) fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub(crate) fn subpackets(&self) -> &[Packet] {
+        &self.subpackets
+    }
+
+    fn check_arity(&self, registry: &Registry) -> Result<(), BitsError> {
+        let definition: &registry::Definition = registry.get(self.operator);
+        match definition.arity.accepts(self.subpackets.len()) {
+            true => Ok(()),
+            false => Err(BitsError::Arity {
+                operator: definition.name,
+                arity: definition.arity,
+                operands: self.subpackets.len(),
+                offset: self.offset,
+            }),
+        }
+    }
+
+    // The length field with its unit, such as `3 sub-packets` or `27 bits`
+    pub(crate) fn describe_length(&self) -> String {
+        match (self.length_type_id, self.length_field) {
+            (true, 1) => "1 sub-packet".to_string(),
+            (true, n) => format!("{} sub-packets", n),
+            (false, n) => format!("{} bits", n),
+        }
+    }
+}
+
+impl Drop for OperatorPacket {
+    fn drop(&mut self) {
+        // Detach the sub-packets first, so each one is dropped without any children left
+        let mut stack: Vec<Packet> = std::mem::take(&mut self.subpackets);
+        while let Some(mut packet) = stack.pop() {
+            if let Packet::Operator(p) = &mut packet {
+                stack.append(&mut p.subpackets);
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
-enum Packet {
+pub(crate) enum Packet {
     Literal(LiteralPacket),
     Operator(OperatorPacket),
 }
 
+impl Packet {
+    pub(crate) fn version(&self) -> u8 {
+        match self {
+            Packet::Literal(p) => p.version,
+            Packet::Operator(p) => p.version,
+        }
+    }
 
-fn hex_char_to_binary(hex_char: &char) -> String {
-    match hex_char {
-        '0' => "0000".to_string(),
-        '1' => "0001".to_string(),
-        '2' => "0010".to_string(),
-        '3' => "0011".to_string(),
-        '4' => "0100".to_string(),
-        '5' => "0101".to_string(),
-        '6' => "0110".to_string(),
-        '7' => "0111".to_string(),
-        '8' => "1000".to_string(),
-        '9' => "1001".to_string(),
-        'A' => "1010".to_string(),
-        'B' => "1011".to_string(),
-        'C' => "1100".to_string(),
-        'D' => "1101".to_string(),
-        'E' => "1110".to_string(),
-        'F' => "1111".to_string(),
-        _ => "".to_string(),
-    }
-}
-
-fn hex_to_binary(hex_str: &str) -> String {
-    return hex_str
-            .chars()
-            .fold(
-                "".to_string(), 
-                |acc, x| acc + &hex_char_to_binary(&x)
-            );
-    
-}
-
-fn parse_packet(s: &str) -> (Packet, u16) {
-    let version: u8 = u8::from_str_radix(&s[0..3], 2).unwrap();
-    let type_id: u8 = u8::from_str_radix(&s[3..6], 2).unwrap();
-    
-    if type_id == 4 {
-        // Parse literal
-        let mut i: usize = 6;
-        let mut string_value: String = String::new();
-
-        while i < s.len() {
-            // Parse the group value
-            string_value = string_value + &s[i+1..i+5];
-            i += 5;
-
-            // Break if the first bit was 0
-            if s.chars().nth(i-5).unwrap() == '0' {
-                break;
-            } 
+    pub(crate) fn offset(&self) -> usize {
+        match self {
+            Packet::Literal(p) => p.offset,
+            Packet::Operator(p) => p.offset,
+        }
+    }
+
+    pub(crate) fn span(&self) -> usize {
+        match self {
+            Packet::Literal(p) => p.span,
+            Packet::Operator(p) => p.span,
         }
+    }
+}
 
-        let value: usize = usize::from_str_radix(&string_value, 2).unwrap();
+impl PartialEq for Packet {
+    fn eq(&self, other: &Self) -> bool {
+        let mut stack: Vec<(&Packet, &Packet)> = vec![(self, other)];
 
-        return (Packet::Literal(LiteralPacket{version, type_id, value}), i as u16);
+        while let Some(pair) = stack.pop() {
+            match pair {
+                (Packet::Literal(a), Packet::Literal(b)) if a == b => {}
+                (Packet::Operator(a), Packet::Operator(b))
+                    if (a.version, a.operator, a.name, a.length_type_id, a.length_field, a.subpackets.len())
+                        == (b.version, b.operator, b.name, b.length_type_id, b.length_field, b.subpackets.len()) =>
+                {
+                    stack.extend(a.subpackets.iter().zip(&b.subpackets));
+                }
+                _ => return false,
+            }
+        }
+        true
+    }
+}
 
-    } else {
-        // Parse operator
-        let length_type_id: bool = s.chars().nth(6).unwrap() == '1';
-        let length_field: u16;
-        let mut subpackets: Vec<Packet> = vec![];
-        let mut i: u16 = 0;
+impl Eq for Packet {}
 
-        if length_type_id {
-            // If the length type ID is 1, then the next 11 bits are a number that represents the number of sub-packets immediately contained by this packet.
-            length_field = u16::from_str_radix(&s[7..18], 2).unwrap();
-            
-            let mut n_read: u16 = 0;
-            while n_read < length_field {
-                let res: (Packet, u16) = parse_packet(&s[(18 + i as usize)..]);
-                
-                subpackets.push(res.0);
-                i += res.1;
-                n_read += 1;
+
+// An operator whose sub-packets are still being parsed
+struct OpenOperator {
+    version: u8,
+    operator: Operator,
+    length_type_id: bool,
+    length_field: usize,
+    subpackets: Vec<Packet>,
+    offset: usize,
+    // Where the sub-packets start, for length type 0
+    body_start: usize,
+}
+
+// Returns the packet and the number of bits it spans. Operators are kept on an explicit
+// stack, so the nesting depth is only limited by memory.
+fn parse_packet(reader: &mut impl BitSource, registry: &Registry) -> Result<(Packet, usize), BitsError> {
+    let mut stack: Vec<OpenOperator> = vec![];
+
+    loop {
+        let start: usize = reader.position();
+        let version: u8 = read_field(reader, 3, start)? as u8;
+        let type_id: u8 = read_field(reader, 3, start)? as u8;
+
+        let mut finished: Option<Packet> = None;
+
+        if type_id == 4 {
+            // Parse literal
+            let mut value: u128 = 0;
+
+            loop {
+                // Parse the group value, the literal has to fit into 128 bits
+                let group: u64 = read_field(reader, 5, start)?;
+                if value >> 124 != 0 {
+                    return Err(BitsError::Overflow { offset: start });
+                }
+                value = (value << 4) | (group & 0b1111) as u128;
+
+                // Break if the first bit was 0
+                if group >> 4 == 0 {
+                    break;
+                }
             }
 
-            i += 18;
-            
+            let span: usize = reader.position() - start;
+            finished = Some(Packet::Literal(LiteralPacket{version, type_id, value, offset: start, span}));
+
         } else {
+            // Parse operator
+            let operator: Operator = Operator::from_type_id(type_id).unwrap();
+            let length_type_id: bool = read_field(reader, 1, start)? == 1;
+
+            // If the length type ID is 1, then the next 11 bits are a number that represents the number of sub-packets immediately contained by this packet.
             // If the length type ID is 0, then the next 15 bits are a number that represents the total length in bits of the sub-packets contained by this packet.
-            length_field = u16::from_str_radix(&s[7..22], 2).unwrap();
+            let length_field: usize = read_field(reader, if length_type_id { 11 } else { 15 }, start)? as usize;
+
+            stack.push(OpenOperator { version, operator, length_type_id, length_field, subpackets: vec![], offset: start, body_start: reader.position() });
+        }
 
-            while i < length_field {
-                let res: (Packet, u16) = parse_packet(&s[(22 + i as usize)..]);
-                
-                subpackets.push(res.0);
-                i += res.1;
+        // Close every operator that has all of its sub-packets, innermost first
+        while let Some(open) = stack.last_mut() {
+            if let Some(packet) = finished.take() {
+                open.subpackets.push(packet);
             }
 
-            i += 22;
+            let consumed: usize = reader.position() - open.body_start;
+            let complete: bool = match open.length_type_id {
+                true => open.subpackets.len() >= open.length_field,
+                false => consumed >= open.length_field,
+            };
+            if !complete {
+                break;
+            }
+
+            let open: OpenOperator = stack.pop().unwrap();
+            if !open.length_type_id && consumed != open.length_field {
+                return Err(BitsError::LengthMismatch { offset: open.offset, length: open.length_field, consumed });
+            }
+
+            let packet: OperatorPacket = OperatorPacket {
+                version: open.version,
+                operator: open.operator,
+                name: registry.get(open.operator).name,
+                length_type_id: open.length_type_id,
+                length_field: open.length_field,
+                subpackets: open.subpackets,
+                offset: open.offset,
+                span: reader.position() - open.offset,
+            };
+            packet.check_arity(registry)?;
+            finished = Some(Packet::Operator(packet));
         }
 
-        return (Packet::Operator(OperatorPacket{version, type_id, length_type_id, length_field, subpackets}), i);
+        if stack.is_empty() {
+            let packet: Packet = finished.unwrap();
+            let span: usize = packet.span();
+            return Ok((packet, span));
+        }
     }
+}
 
+fn read_field(reader: &mut impl BitSource, n: usize, packet_offset: usize) -> Result<u64, BitsError> {
+    reader.read_bits(n).ok_or(BitsError::Truncated { offset: packet_offset })
+}
+
+struct VersionSum;
+
+impl Visitor for VersionSum {
+    type Output = usize;
+    type Error = Infallible;
+
+    fn leave(&mut self, packet: &Packet, _depth: usize, versions: Vec<usize>) -> Result<usize, Infallible> {
+        Ok(packet.version() as usize + versions.iter().sum::<usize>())
+    }
 }
 
 fn sum_versions(packet: &Packet) -> usize {
-    match packet {
-        Packet::Literal(p) => p.version as usize,
-        Packet::Operator(p) => {
-                p
-                .subpackets
-                .iter()
-                .fold(
-                    p.version as usize, 
-                    |acc, x| acc + sum_versions(x)
-                )
-        }
-    }
-}
-
-fn evaluate(packet: &Packet) -> usize {
-    match packet {
-        Packet::Literal(p) => p.value as usize,
-        Packet::Operator(p) => {
-            if p.type_id == 0 {
-                // 0 - sum
-                p.subpackets.iter().fold(0, |acc, x| acc + evaluate(x))
-            } else if p.type_id == 1 {
-                // 1 - product
-                p.subpackets.iter().fold(1, |acc, x| acc * evaluate(x))
-            } else if p.type_id == 2 {
-                // 2 - minimum
-                p.subpackets.iter().fold(usize::MAX, |acc, x| min(acc, evaluate(x)))
-            } else if p.type_id == 3 {
-                // 3 - maximum
-                p.subpackets.iter().fold(0, |acc, x| max(acc, evaluate(x)))
-            } else if p.type_id == 5 {
-                // 5 - greater than
-                (evaluate(&p.subpackets[0]) > evaluate(&p.subpackets[1])) as usize
-            } else if p.type_id == 6 {
-                // 6 - less than
-                (evaluate(&p.subpackets[0]) < evaluate(&p.subpackets[1])) as usize
-            } else if p.type_id == 7 {
-                // 7 - equal
-                (evaluate(&p.subpackets[0]) == evaluate(&p.subpackets[1])) as usize
-            } else {
-                0
-            }
+    let Ok(sum) = walk(packet, &mut VersionSum);
+    sum
+}
+
+// Applies the operators of `registry`. Fails with the offset of the first packet without a
+// value, such as one that doesn't fit into 128 bits.
+struct Evaluator<'a> {
+    registry: &'a Registry,
+}
+
+impl Visitor for Evaluator<'_> {
+    type Output = u128;
+    type Error = BitsError;
+
+    fn leave(&mut self, packet: &Packet, _depth: usize, values: Vec<u128>) -> Result<u128, BitsError> {
+        let p: &OperatorPacket = match packet {
+            Packet::Literal(p) => return Ok(p.value),
+            Packet::Operator(p) => p,
+        };
+        p.check_arity(self.registry)?;
+
+        (self.registry.get(p.operator).eval)(&values).map_err(|fault| match fault {
+            EvalFault::Overflow => BitsError::Overflow { offset: p.offset },
+            EvalFault::Undefined => BitsError::Undefined { offset: p.offset },
+        })
+    }
+}
+
+fn evaluate(packet: &Packet, registry: &Registry) -> Result<u128, BitsError> {
+    walk(packet, &mut Evaluator { registry })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bits::{bytes_to_hex, hex_to_bytes, BitWriter};
+
+    #[test]
+    fn part_1_works() {
+        for (input, expected) in p1_data() {
+            let result = part_1(Transmission::hex(input));
+
+            assert_eq!(result, expected, "input {}", input);
         }
     }
+
+    #[test]
+    fn part_2_works() {
+        for (input, expected) in p2_data() {
+            let result = part_2(Transmission::hex(input));
+
+            assert_eq!(result, expected, "input {}", input);
+        }
+    }
+
+    #[test]
+    fn parts_read_every_encoding() {
+        let hex = "8A004A801A8002F478";
+        let auto = |bytes: &'static [u8]| Transmission::from_input(bytes, Encoding::Auto);
+
+        assert_eq!(part_1(Transmission { bytes: b"igBKgBqAAvR4\n", encoding: Encoding::Base64 }), part_1(Transmission::hex(hex)));
+        assert_eq!(part_1(Transmission { bytes: &hex_to_bytes(hex), encoding: Encoding::Binary }), part_1(Transmission::hex(hex)));
+        assert_eq!(part_1(auto(b"igBKgBqAAvR4\n").unwrap()), part_1(Transmission::hex(hex)));
+        assert_eq!(part_2(auto(&[0x9C, 0x01, 0x41, 0x08, 0x02, 0x50, 0x32, 0x0F, 0x18, 0x02, 0x10, 0x4A, 0x08]).unwrap()), 1);
+        assert_eq!(evaluate_input(b"", &DecodeOptions::default()), Err(BitsError::Truncated { offset: 0 }));
+    }
+
+    #[test]
+    fn ambiguous_encoding_is_rejected() {
+        // 8 hex digits are also 2 groups of base64 characters
+        assert_eq!(Encoding::detect(b"C200B40A\n"), Err(BitsError::AmbiguousEncoding));
+        assert_eq!(Transmission::from_input(b"C200B40A\n", Encoding::Auto).unwrap_err(), "both hex and base64, pick an encoding");
+        assert_eq!(part_2(Transmission::from_input(b"C200B40A82\n", Encoding::Auto).unwrap()), 3);
+        assert_eq!(
+            PacketStream::new("C200B40A".as_bytes(), DecodeOptions { encoding: Encoding::Auto, ..DecodeOptions::default() }).next(),
+            Some(Err(BitsError::AmbiguousEncoding))
+        );
+    }
+
+    #[test]
+    fn invalid_arity_is_rejected() {
+        // Less than with a single operand, and a sum without any
+        assert_eq!(decode("3A004428"), Err(BitsError::Arity { operator: "lt", arity: Arity::Exactly(2), operands: 1, offset: 0 }));
+        assert_eq!(decode("000000"), Err(BitsError::Arity { operator: "sum", arity: Arity::AtLeast(1), operands: 0, offset: 0 }));
+        assert_eq!(
+            BitsError::Arity { operator: "lt", arity: Arity::Exactly(2), operands: 1, offset: 0 }.to_string(),
+            "lt at bit 0 needs exactly 2 operands, got 1"
+        );
+    }
+
+    #[test]
+    fn custom_operators_work() {
+        let mut registry = Registry::builtin();
+        registry.register(5, "xor", Arity::AtLeast(2), Box::new(|values| Ok(values.iter().fold(0, |acc, v| acc ^ v)))).unwrap();
+        registry
+            .register(6, "mod", Arity::Exactly(2), Box::new(|values| values[0].checked_rem(values[1]).ok_or(EvalFault::Undefined)))
+            .unwrap();
+        let options = DecodeOptions { registry: Arc::new(registry), ..DecodeOptions::default() };
+
+        assert_eq!(evaluate_input(compile("gt(12, 10)").unwrap().as_bytes(), &options), Ok(6));
+        assert_eq!(evaluate_input(compile("sum(lt(17, 5), 1)").unwrap().as_bytes(), &options), Ok(3));
+        assert_eq!(evaluate_input(compile("lt(17, 0)").unwrap().as_bytes(), &options), Err(BitsError::Undefined { offset: 0 }));
+
+        // Decoding checks the arity of the registered operator: gt, now xor, of 1, 2 and 4
+        let mut writer = BitWriter::new();
+        writer.write_bits(0, 3);
+        writer.write_bits(5, 3);
+        writer.write_bit(true);
+        writer.write_bits(3, 11);
+        for value in [1, 2, 4] {
+            writer.write_bits(0, 3);
+            writer.write_bits(4, 3);
+            writer.write_bits(value, 5);
+        }
+        let hex = bytes_to_hex(&writer.into_bytes());
+
+        assert_eq!(evaluate_input(hex.as_bytes(), &options), Ok(7));
+        assert_eq!(
+            evaluate_input(hex.as_bytes(), &DecodeOptions::default()).unwrap_err().to_string(),
+            "gt at bit 0 needs exactly 2 operands, got 3"
+        );
+
+        // The packets carry the registered names, and every output uses them
+        let gt: String = compile("gt(12, 10)").unwrap();
+        let Packet::Operator(packet) = first_packet(gt.as_bytes(), &options).unwrap() else { panic!() };
+        assert_eq!((packet.operator, packet.name()), (Operator::Gt, "xor"));
+        assert_ne!(Packet::Operator(packet), decode(&gt).unwrap());
+        assert!(emit(hex.as_bytes(), "disasm", &options).unwrap().contains(" v0 xor (length type 1: 3 sub-packets)\n"));
+        assert!(emit(hex.as_bytes(), "stats", &options).unwrap().contains("\n  xor      1\n"));
+        assert!(emit(hex.as_bytes(), "json", &options).unwrap().contains("\"type\":\"xor\""));
+        assert!(emit(hex.as_bytes(), "trace", &options).unwrap().ends_with("value 7 = xor(1, 2, 4)\n"));
+    }
+
+    #[test]
+    fn long_literals_work() {
+        assert_eq!(part_2(Transmission::hex("121FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFDE0")), u128::MAX);
+        assert_eq!(decode("123FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFDE0"), Err(BitsError::Overflow { offset: 0 }));
+    }
+
+    #[test]
+    fn overflow_is_reported() {
+        let packet = decode(&compile("sum(1, product(18446744073709551616, 18446744073709551616))").unwrap()).unwrap();
+
+        assert_eq!(evaluate(&packet, Registry::standard()), Err(BitsError::Overflow { offset: 18 + 11 }));
+
+        let packet = decode(&compile("max(sum(2, 3), sum(340282366920938463463374607431768211455, 1))").unwrap()).unwrap();
+
+        assert_eq!(evaluate(&packet, Registry::standard()), Err(BitsError::Overflow { offset: 18 + 18 + 2 * 11 }));
+    }
+
+    #[test]
+    fn long_transmissions_work() {
+        // 1000 literals of 86 bits each, well beyond what a 16 bit offset can address
+        let source: String = format!("sum({})", vec![u64::MAX.to_string(); 1000].join(", "));
+        let hex: String = compile(&source).unwrap();
+        let Packet::Operator(packet) = decode(&hex).unwrap() else { panic!() };
+
+        assert_eq!(packet.span, 18 + 1000 * 86);
+        assert_eq!(packet.subpackets[999].offset(), 18 + 999 * 86);
+        assert_eq!(part_2(Transmission::hex(&hex)), 1000 * u64::MAX as u128);
+        assert_eq!(encode(&Packet::Operator(packet)).unwrap(), hex);
+    }
+
+    fn decode_strict(input: &str) -> Result<Packet, BitsError> {
+        PacketStream::new(input.as_bytes(), DecodeOptions { strict: true, ..DecodeOptions::default() }).next().unwrap()
+    }
+
+    #[test]
+    fn strict_decode_works() {
+        for (input, _) in p2_data() {
+            assert_eq!(decode_strict(&format!("{}\n", input)), decode(input));
+        }
+    }
+
+    #[test]
+    fn malformed_transmissions_are_rejected() {
+        assert_eq!(decode_strict("D2FE2x"), Err(BitsError::InvalidCharacter { character: 'x', position: 5 }));
+        assert_eq!(decode_strict("D2_FE28"), Err(BitsError::InvalidCharacter { character: '_', position: 2 }));
+        assert_eq!(decode_strict("D2FE29"), Err(BitsError::NonZeroPadding { position: 23 }));
+        assert_eq!(decode("D2FE29").unwrap(), decode("D2FE28").unwrap());
+
+        // The second literal of the `lt` is cut off, in both modes
+        assert_eq!(decode("38006F452912"), Err(BitsError::Truncated { offset: 33 }));
+        assert_eq!(decode_strict("38006F"), Err(BitsError::Truncated { offset: 22 }));
+
+        // A length field of 26 bits for sub-packets of 11 and 16 bits
+        assert_eq!(decode("38006B45291200"), Err(BitsError::LengthMismatch { offset: 0, length: 26, consumed: 27 }));
+    }
+
+    #[test]
+    fn deep_nesting_works() {
+        // Sums of a single sub-packet, nested far deeper than the call stack allows
+        const DEPTH: usize = 100_000;
+        let mut writer = BitWriter::new();
+        for _ in 0..DEPTH {
+            writer.write_bits(1, 3);
+            writer.write_bits(0, 3);
+            writer.write_bit(true);
+            writer.write_bits(1, 11);
+        }
+        writer.write_bits(1, 3);
+        writer.write_bits(4, 3);
+        writer.write_bits(7, 5);
+        let hex = bytes_to_hex(&writer.into_bytes());
+
+        assert_eq!(part_1(Transmission::hex(&hex)), DEPTH + 1);
+        assert_eq!(part_2(Transmission::hex(&hex)), 7);
+
+        let packet = decode(&hex).unwrap();
+
+        assert_eq!(encode(&packet).unwrap(), hex);
+        assert_eq!(packet, decode(&hex).unwrap());
+    }
+
+    #[test]
+    fn emit_works() {
+        let options = DecodeOptions::default();
+
+        assert_eq!(emit(b"D2FE28\n38006F45291200\n", "hex", &options).unwrap(), "D2FE28\n38006F45291200");
+        assert_eq!(emit(b"D2FE28 D2FE", "hex", &options).unwrap_err(), "transmission 2: packet at bit 0 is truncated");
+        assert_eq!(emit(b"C200B40A82", "optimized:flatten,collapse", &options).unwrap(), "C200B40A82");
+        assert_eq!(emit(b"C200B40A82", "optimized", &options).unwrap(), "D060");
+        assert_eq!(emit(b"D2FE28 D2FE28", "json", &options).unwrap().matches("\"value\":2021").count(), 2);
+        assert_eq!(emit(b"", "xml", &options).unwrap_err(), "Unknown output format xml");
+        assert_eq!(emit(b"", "optimized:inline", &options).unwrap_err(), "Unknown optimization pass inline");
+    }
+
+    fn p1_data() -> Vec<(&'static str, usize)> {
+        vec![
+            ("D2FE28", 6),
+            ("38006F45291200", 9),
+            ("EE00D40C823060", 14),
+            ("8A004A801A8002F478", 16),
+            ("620080001611562C8802118E34", 12),
+            ("C0015000016115A2E0802F182340", 23),
+            ("A0016C880162017C3686B18A3D4780", 31),
+        ]
+    }
+
+    fn p2_data() -> Vec<(&'static str, u128)> {
+        vec![
+            ("D2FE28", 2021),
+            ("C200B40A82", 3),
+            ("04005AC33890", 54),
+            ("880086C3E88112", 7),
+            ("CE00C43D881120", 9),
+            ("D8005AC2A8F0", 1),
+            ("F600BC2D8F", 0),
+            ("9C005AC2F8F0", 0),
+            ("9C0141080250320F1802104A08", 1),
+        ]
+    }
 }
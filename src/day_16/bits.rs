@@ -0,0 +1,102 @@
+// Where `parse_packet` reads a transmission from, bit by bit
+pub trait BitSource {
+    // Number of bits read since the start of the transmission
+    fn position(&self) -> usize;
+
+    // Reads the next `n <= 64` bits as an unsigned number, `None` if fewer than `n` bits are left
+    fn read_bits(&mut self, n: usize) -> Option<u64>;
+
+    fn read_bit(&mut self) -> Option<bool> {
+        self.read_bits(1).map(|b| b == 1)
+    }
+}
+
+// Writes big-endian bit fields, in the order `parse_packet` reads them
+#[derive(Default)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    position: usize,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        BitWriter::default()
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    // Appends the lowest `n <= 64` bits of `value`
+    pub fn write_bits(&mut self, value: u64, n: usize) {
+        self.bytes.resize((self.position + n).div_ceil(8), 0);
+        self.position += n;
+        self.write_bits_at(self.position - n, value, n);
+    }
+
+    pub fn write_bit(&mut self, bit: bool) {
+        self.write_bits(bit as u64, 1);
+    }
+
+    // Overwrites `n` already written bits starting at `position`
+    pub fn write_bits_at(&mut self, position: usize, value: u64, n: usize) {
+        assert!(n <= 64 && position + n <= self.position);
+
+        for i in 0..n {
+            let bit: u8 = ((value >> (n - 1 - i)) & 1) as u8;
+            let p: usize = position + i;
+            let mask: u8 = 0x80 >> (p % 8);
+
+            self.bytes[p / 8] = (self.bytes[p / 8] & !mask) | (bit * mask);
+        }
+    }
+
+    // The written bits, zero-padded to a whole byte
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+// Decodes the hexadecimal characters of `hex_str`, ignoring anything else. An odd
+// trailing nibble fills the high half of the last byte. Only tests build binary input this way.
+#[cfg(test)]
+pub fn hex_to_bytes(hex_str: &str) -> Vec<u8> {
+    let nibbles: Vec<u8> = hex_str
+        .chars()
+        .filter_map(|c| c.to_digit(16))
+        .map(|d| d as u8)
+        .collect();
+
+    nibbles
+        .chunks(2)
+        .map(|pair| (pair[0] << 4) | pair.get(1).copied().unwrap_or(0))
+        .collect()
+}
+
+pub fn bytes_to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_bits_works() {
+        let mut writer = BitWriter::new();
+        writer.write_bits(6, 3);
+        writer.write_bits(4, 3);
+        writer.write_bits(0, 15);
+        writer.write_bits(0b101_1111_1100_0101, 15);
+        writer.write_bits_at(6, 0b101, 15);
+
+        assert_eq!(writer.position(), 36);
+        assert_eq!(bytes_to_hex(&writer.into_bytes()), "D0002DFC50");
+    }
+
+    #[test]
+    fn hex_to_bytes_works() {
+        assert_eq!(hex_to_bytes("8A004a\n"), vec![0x8A, 0x00, 0x4A]);
+        assert_eq!(hex_to_bytes("ABC"), vec![0xAB, 0xC0]);
+    }
+}
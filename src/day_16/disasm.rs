@@ -0,0 +1,59 @@
+use super::Packet;
+
+// One line per packet, indented by depth, with operators named after the registry they were decoded with:
+//     <bit offset> +<span in bits>  v<version> <type> <literal value or length field>
+pub fn disassemble(packet: &Packet) -> String {
+    let mut out: String = String::new();
+
+    // Pre-order from an explicit stack, so the nesting depth is only limited by memory
+    let mut stack: Vec<(&Packet, usize)> = vec![(packet, 0)];
+    while let Some((packet, depth)) = stack.pop() {
+        out.push_str(&format!("{:>6} +{:<6}", packet.offset(), packet.span()));
+        out.push_str(&"  ".repeat(depth));
+
+        match packet {
+            Packet::Literal(p) => out.push_str(&format!("v{} literal {}\n", packet.version(), p.value())),
+            Packet::Operator(p) => {
+                out.push_str(&format!(
+                    "v{} {} (length type {}: {})\n",
+                    packet.version(),
+                    p.name(),
+                    p.length_type_id as u8,
+                    p.describe_length()
+                ));
+
+                stack.extend(p.subpackets().iter().rev().map(|subpacket| (subpacket, depth + 1)));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day_16::decode;
+
+    fn disassemble_hex(hex: &str) -> String {
+        disassemble(&decode(hex).unwrap())
+    }
+
+    #[test]
+    fn disassemble_works() {
+        assert_eq!(disassemble_hex("8A004A801A8002F478"), "     \
+     0 +69    v4 min (length type 1: 1 sub-packet)
+    18 +51      v1 min (length type 1: 1 sub-packet)
+    36 +33        v5 min (length type 0: 11 bits)
+    58 +11          v6 literal 15
+");
+    }
+
+    #[test]
+    fn offsets_follow_the_transmission() {
+        assert_eq!(disassemble_hex("38006F45291200"), "     \
+     0 +49    v1 lt (length type 0: 27 bits)
+    22 +11      v6 literal 10
+    33 +16      v2 literal 20
+");
+    }
+}
@@ -0,0 +1,168 @@
+use std::convert::Infallible;
+
+use super::bits::{bytes_to_hex, BitWriter};
+use super::visit::{walk, Visitor};
+use super::{BitsError, Packet};
+
+pub(super) const MAX_SUBPACKET_COUNT: usize = (1 << 11) - 1;
+pub(super) const MAX_SUBPACKET_BITS: usize = (1 << 15) - 1;
+
+// Serializes `packet` into a hex transmission, zero-padded to a whole byte.
+// Length fields are computed from the sub-packets, the stored `length_field` is ignored.
+pub fn encode(packet: &Packet) -> Result<String, BitsError> {
+    let mut writer: BitWriter = BitWriter::new();
+    write_packet(&mut writer, packet)?;
+
+    Ok(bytes_to_hex(&writer.into_bytes()))
+}
+
+enum Step<'a> {
+    Write(&'a Packet),
+    // Fills in the 15 bit length field at the given position once the sub-packets are written
+    PatchLength(usize),
+}
+
+// Packets are written from an explicit stack, so the nesting depth is only limited by memory
+fn write_packet(writer: &mut BitWriter, packet: &Packet) -> Result<(), BitsError> {
+    let mut stack: Vec<Step> = vec![Step::Write(packet)];
+
+    while let Some(step) = stack.pop() {
+        match step {
+            Step::Write(Packet::Literal(p)) => {
+                writer.write_bits(p.version as u64, 3);
+                writer.write_bits(4, 3);
+
+                // Groups of 4 bits, most significant first, each prefixed by 1 except for the last one
+                for group in (0..literal_groups(p.value)).rev() {
+                    writer.write_bit(group > 0);
+                    writer.write_bits(((p.value >> (4 * group)) & 0b1111) as u64, 4);
+                }
+            }
+            Step::Write(Packet::Operator(p)) => {
+                writer.write_bits(p.version as u64, 3);
+                writer.write_bits(p.operator.type_id() as u64, 3);
+                writer.write_bit(p.length_type_id);
+
+                if p.length_type_id {
+                    if p.subpackets.len() > MAX_SUBPACKET_COUNT {
+                        return Err(BitsError::LengthOverflow { length_type_id: true, length: p.subpackets.len() });
+                    }
+                    writer.write_bits(p.subpackets.len() as u64, 11);
+                } else {
+                    // Reserve the length field and fill it in once the sub-packets are written
+                    stack.push(Step::PatchLength(writer.position()));
+                    writer.write_bits(0, 15);
+                }
+
+                stack.extend(p.subpackets.iter().rev().map(Step::Write));
+            }
+            Step::PatchLength(length_position) => {
+                let length: usize = writer.position() - length_position - 15;
+                if length > MAX_SUBPACKET_BITS {
+                    return Err(BitsError::LengthOverflow { length_type_id: false, length });
+                }
+                writer.write_bits_at(length_position, length as u64, 15);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Number of 4 bit groups needed for `value`, at least one
+fn literal_groups(value: u128) -> usize {
+    ((u128::BITS - value.leading_zeros()) as usize).div_ceil(4).max(1)
+}
+
+struct EncodedLength;
+
+impl Visitor for EncodedLength {
+    type Output = usize;
+    type Error = Infallible;
+
+    fn leave(&mut self, packet: &Packet, _depth: usize, lengths: Vec<usize>) -> Result<usize, Infallible> {
+        Ok(match packet {
+            Packet::Literal(p) => 6 + 5 * literal_groups(p.value),
+            Packet::Operator(p) => {
+                let length_bits: usize = if p.length_type_id { 11 } else { 15 };
+                7 + length_bits + lengths.iter().sum::<usize>()
+            }
+        })
+    }
+}
+
+// Number of bits `encode` writes for `packet`, without the padding
+pub fn encoded_len(packet: &Packet) -> usize {
+    let Ok(length) = walk(packet, &mut EncodedLength);
+    length
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day_16::{LiteralPacket, Operator, OperatorPacket};
+
+    fn decode(hex: &str) -> Packet {
+        crate::day_16::decode(hex).unwrap()
+    }
+
+    fn literal(value: u128) -> Packet {
+        Packet::Literal(LiteralPacket { version: 1, type_id: 4, value, offset: 0, span: 0 })
+    }
+
+    fn operator(operator: Operator, length_type_id: bool, subpackets: Vec<Packet>) -> Packet {
+        let length_field: usize = match length_type_id {
+            true => subpackets.len(),
+            false => subpackets.iter().map(encoded_len).sum::<usize>(),
+        };
+        Packet::Operator(OperatorPacket { version: 7, operator, name: operator.name(), length_type_id, length_field, subpackets, offset: 0, span: 0 })
+    }
+
+    #[test]
+    fn puzzle_examples_round_trip() {
+        for hex in ["D2FE28", "38006F45291200", "EE00D40C823060", "8A004A801A8002F478", "9C0141080250320F1802104A08"] {
+            assert_eq!(encode(&decode(hex)).unwrap(), hex);
+        }
+    }
+
+    #[test]
+    fn literal_groups_work() {
+        for value in [0, 1, 15, 16, 2021, 1 << 40, u64::MAX as u128 + 1, u128::MAX] {
+            let packet = literal(value);
+
+            assert_eq!(decode(&encode(&packet).unwrap()), packet);
+        }
+        assert_eq!(encode(&literal(0)).unwrap(), "3000");
+    }
+
+    #[test]
+    fn both_length_types_round_trip() {
+        let packet = operator(Operator::Sum, false, vec![
+            literal(3),
+            operator(Operator::Product, true, vec![literal(2021), literal(0)]),
+            operator(Operator::Eq, false, vec![literal(5), literal(5)]),
+        ]);
+
+        assert_eq!(decode(&encode(&packet).unwrap()), packet);
+    }
+
+    #[test]
+    fn encoded_len_works() {
+        for hex in ["D2FE28", "38006F45291200", "EE00D40C823060", "9C0141080250320F1802104A08"] {
+            let packet = decode(hex);
+
+            assert_eq!(encoded_len(&packet), packet.span());
+        }
+    }
+
+    #[test]
+    fn length_overflow_is_rejected() {
+        let packet = operator(Operator::Sum, true, (0..2048).map(literal).collect());
+
+        assert_eq!(encode(&packet), Err(BitsError::LengthOverflow { length_type_id: true, length: 2048 }));
+
+        let packet = operator(Operator::Sum, false, (0..3000).map(literal).collect());
+
+        assert!(matches!(encode(&packet), Err(BitsError::LengthOverflow { length_type_id: false, .. })));
+    }
+}
@@ -0,0 +1,205 @@
+use std::convert::Infallible;
+
+use super::registry::Registry;
+use super::visit::{walk, Visitor};
+use super::{Evaluator, Packet};
+
+// The value of a packet from the values of its sub-packets, `None` if any of them or the
+// packet itself can't be evaluated
+fn value(registry: &Registry, packet: &Packet, depth: usize, values: Vec<Option<u128>>) -> Option<u128> {
+    let values: Vec<u128> = values.into_iter().collect::<Option<Vec<u128>>>()?;
+    Evaluator { registry }.leave(packet, depth, values).ok()
+}
+
+// Operators are named after what they did in the registry they were decoded with
+fn type_name(packet: &Packet) -> &'static str {
+    match packet {
+        Packet::Literal(_) => "literal",
+        Packet::Operator(p) => p.name(),
+    }
+}
+
+// `s` inside a JSON string
+fn escape_json(s: &str) -> String {
+    let mut out: String = String::new();
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// `s` inside a quoted DOT label, where a backslash starts an escape sequence such as `\n`
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+// One object per line, indented by depth, so two transmissions can be compared with `diff`.
+// The value comes last, as it is only known after the sub-packets; it is `null` where
+// evaluation fails. Values are written as plain numbers even above 2^53.
+struct Json<'a> {
+    registry: &'a Registry,
+    out: String,
+    // Whether the operators on the current path have written a sub-packet yet
+    open: Vec<bool>,
+}
+
+impl Visitor for Json<'_> {
+    type Output = Option<u128>;
+    type Error = Infallible;
+
+    fn enter(&mut self, packet: &Packet, depth: usize) -> Result<(), Infallible> {
+        if let Some(written) = self.open.last_mut() {
+            self.out.push_str(if *written { ",\n" } else { "\n" });
+            self.out.push_str(&"  ".repeat(depth));
+            *written = true;
+        }
+
+        self.out.push_str(&format!(
+            "{{\"offset\":{},\"span\":{},\"version\":{},\"type\":\"{}\"",
+            packet.offset(),
+            packet.span(),
+            packet.version(),
+            escape_json(type_name(packet))
+        ));
+        if let Packet::Operator(p) = packet {
+            self.out.push_str(&format!(
+                ",\"length_type_id\":{},\"length\":{},\"subpackets\":[",
+                p.length_type_id as u8, p.length_field
+            ));
+            self.open.push(false);
+        }
+        Ok(())
+    }
+
+    fn leave(&mut self, packet: &Packet, depth: usize, values: Vec<Option<u128>>) -> Result<Option<u128>, Infallible> {
+        if let Packet::Operator(_) = packet {
+            if self.open.pop().unwrap() {
+                self.out.push('\n');
+                self.out.push_str(&"  ".repeat(depth));
+            }
+            self.out.push(']');
+        }
+
+        let value: Option<u128> = value(self.registry, packet, depth, values);
+        match value {
+            Some(v) => self.out.push_str(&format!(",\"value\":{}}}", v)),
+            None => self.out.push_str(",\"value\":null}"),
+        }
+        Ok(value)
+    }
+}
+
+pub fn to_json(packet: &Packet, registry: &Registry) -> String {
+    let mut json: Json = Json { registry, out: String::new(), open: vec![] };
+    let Ok(_) = walk(packet, &mut json);
+    json.out.push('\n');
+    json.out
+}
+
+// A Graphviz digraph with one box per packet, named by its position in pre-order. Edges are
+// written when a packet is entered and the boxes when it is left, once the value is known.
+struct Dot<'a> {
+    registry: &'a Registry,
+    out: String,
+    next_id: usize,
+    // Ids of the packets on the current path
+    path: Vec<usize>,
+}
+
+impl Visitor for Dot<'_> {
+    type Output = Option<u128>;
+    type Error = Infallible;
+
+    fn enter(&mut self, _packet: &Packet, _depth: usize) -> Result<(), Infallible> {
+        if let Some(parent) = self.path.last() {
+            self.out.push_str(&format!("  p{} -> p{};\n", parent, self.next_id));
+        }
+        self.path.push(self.next_id);
+        self.next_id += 1;
+        Ok(())
+    }
+
+    fn leave(&mut self, packet: &Packet, depth: usize, values: Vec<Option<u128>>) -> Result<Option<u128>, Infallible> {
+        let id: usize = self.path.pop().unwrap();
+        let value: Option<u128> = value(self.registry, packet, depth, values);
+
+        let mut label: String =
+            format!("v{} {}\\nbit {} +{}", packet.version(), escape_dot(type_name(packet)), packet.offset(), packet.span());
+        if let Packet::Operator(p) = packet {
+            label.push_str(&format!("\\nlength type {}: {}", p.length_type_id as u8, p.describe_length()));
+        }
+        match value {
+            Some(v) => label.push_str(&format!("\\n= {}", v)),
+            None => label.push_str("\\n= error"),
+        }
+        self.out.push_str(&format!("  p{} [label=\"{}\"];\n", id, label));
+        Ok(value)
+    }
+}
+
+pub fn to_dot(packet: &Packet, registry: &Registry) -> String {
+    let mut dot: Dot = Dot { registry, out: String::from("digraph bits {\n  node [shape=box];\n"), next_id: 0, path: vec![] };
+    let Ok(_) = walk(packet, &mut dot);
+    dot.out.push_str("}\n");
+    dot.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day_16::registry::Arity;
+    use crate::day_16::{compile, decode, first_packet, DecodeOptions};
+    use std::sync::Arc;
+
+    #[test]
+    fn json_works() {
+        assert_eq!(to_json(&decode("D2FE28").unwrap(), Registry::standard()), "{\"offset\":0,\"span\":21,\"version\":6,\"type\":\"literal\",\"value\":2021}\n");
+        assert_eq!(to_json(&decode("38006F45291200").unwrap(), Registry::standard()), "\
+{\"offset\":0,\"span\":49,\"version\":1,\"type\":\"lt\",\"length_type_id\":0,\"length\":27,\"subpackets\":[
+  {\"offset\":22,\"span\":11,\"version\":6,\"type\":\"literal\",\"value\":10},
+  {\"offset\":33,\"span\":16,\"version\":2,\"type\":\"literal\",\"value\":20}
+],\"value\":1}
+");
+    }
+
+    #[test]
+    fn failed_evaluation_is_null() {
+        // sum(u128::MAX, 1)
+        let json = to_json(&decode(&compile("sum(340282366920938463463374607431768211455, 1)").unwrap()).unwrap(), Registry::standard());
+
+        assert!(json.ends_with("],\"value\":null}\n"));
+        assert!(json.contains("\"value\":340282366920938463463374607431768211455}"));
+    }
+
+    #[test]
+    fn names_are_escaped() {
+        let mut registry = Registry::builtin();
+        registry.register(5, "a\"b\\c", Arity::Exactly(2), Box::new(|_| Ok(0))).unwrap();
+        let options = DecodeOptions { registry: Arc::new(registry), ..DecodeOptions::default() };
+        let packet = first_packet(compile("gt(1, 2)").unwrap().as_bytes(), &options).unwrap();
+
+        assert!(to_json(&packet, Registry::standard()).contains(",\"type\":\"a\\\"b\\\\c\","));
+        assert!(to_dot(&packet, Registry::standard()).contains("[label=\"v0 a\\\"b\\\\c\\nbit 0"));
+        assert_eq!(escape_json("\n\u{1}"), "\\u000a\\u0001");
+        assert_eq!(escape_dot("a\nb"), "a\\nb");
+    }
+
+    #[test]
+    fn dot_works() {
+        assert_eq!(to_dot(&decode("38006F45291200").unwrap(), Registry::standard()), "\
+digraph bits {
+  node [shape=box];
+  p0 -> p1;
+  p1 [label=\"v6 literal\\nbit 22 +11\\n= 10\"];
+  p0 -> p2;
+  p2 [label=\"v2 literal\\nbit 33 +16\\n= 20\"];
+  p0 [label=\"v1 lt\\nbit 0 +49\\nlength type 0: 27 bits\\n= 1\"];
+}
+");
+    }
+}
@@ -0,0 +1,159 @@
+use super::encoder::encode;
+use super::{LiteralPacket, Operator, OperatorPacket, Packet};
+
+// Compiles an expression such as `sum(1, product(2, 3), min@5(7, 8))` into a hex transmission.
+// `@N` after a number or an operator name sets the version of that packet, which defaults to 0.
+pub fn compile(source: &str) -> Result<String, String> {
+    let packet: Packet = parse_expression(source)?;
+    encode(&packet).map_err(|e| e.to_string())
+}
+
+// Operators are encoded with the sub-packet count as length field (length type ID 1)
+pub fn parse_expression(source: &str) -> Result<Packet, String> {
+    let mut parser: Parser = Parser { chars: source.chars().collect(), position: 0 };
+    let packet: Packet = parser.expression()?;
+
+    if parser.peek().is_some() {
+        return Err(parser.error("expected end of expression"));
+    }
+    Ok(packet)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    // Skips whitespace and returns the next character without consuming it
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.get(self.position).is_some_and(|c| c.is_whitespace()) {
+            self.position += 1;
+        }
+        self.chars.get(self.position).copied()
+    }
+
+    fn error(&self, message: &str) -> String {
+        error_at(self.position, message)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.position += 1;
+                Ok(())
+            }
+            Some(c) => Err(self.error(&format!("expected '{}', found '{}'", expected, c))),
+            None => Err(self.error(&format!("expected '{}', found end of expression", expected))),
+        }
+    }
+
+    fn word(&mut self, predicate: fn(&char) -> bool) -> String {
+        let start: usize = self.position;
+        while self.chars.get(self.position).is_some_and(predicate) {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().collect()
+    }
+
+    fn expression(&mut self) -> Result<Packet, String> {
+        match self.peek() {
+            Some(c) if c.is_ascii_digit() => {
+                let start: usize = self.position;
+                let digits: String = self.word(char::is_ascii_digit);
+                let value: u128 = digits.parse().map_err(|_| error_at(start, &format!("literal {} is too large", digits)))?;
+                let version: u8 = self.version()?;
+
+                Ok(Packet::Literal(LiteralPacket { version, type_id: 4, value, offset: 0, span: 0 }))
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let start: usize = self.position;
+                let name: String = self.word(char::is_ascii_alphanumeric);
+                let Some(operator) = Operator::ALL.into_iter().find(|o| o.name() == name) else {
+                    return Err(error_at(start, &format!("unknown operator {}", name)));
+                };
+                let version: u8 = self.version()?;
+
+                self.expect('(')?;
+                let mut subpackets: Vec<Packet> = vec![];
+                if self.peek() != Some(')') {
+                    loop {
+                        subpackets.push(self.expression()?);
+                        if self.peek() != Some(',') {
+                            break;
+                        }
+                        self.position += 1;
+                    }
+                }
+                self.expect(')')?;
+
+                match (operator.accepts(subpackets.len()), operator.is_comparison()) {
+                    (true, _) => {}
+                    (false, true) => return Err(error_at(start, &format!("{} needs exactly 2 operands, got {}", name, subpackets.len()))),
+                    (false, false) => return Err(error_at(start, &format!("{} needs at least 1 operand", name))),
+                }
+
+                let length_field: usize = subpackets.len();
+                Ok(Packet::Operator(OperatorPacket { version, operator, name: operator.name(), length_type_id: true, length_field, subpackets, offset: 0, span: 0 }))
+            }
+            Some(c) => Err(self.error(&format!("expected a number or an operator, found '{}'", c))),
+            None => Err(self.error("unexpected end of expression")),
+        }
+    }
+
+    fn version(&mut self) -> Result<u8, String> {
+        if self.peek() != Some('@') {
+            return Ok(0);
+        }
+        self.position += 1;
+
+        self.peek();
+        let start: usize = self.position;
+        match self.word(char::is_ascii_digit).parse::<u8>() {
+            Ok(version) if version < 8 => Ok(version),
+            _ => Err(error_at(start, "expected a version between 0 and 7")),
+        }
+    }
+}
+
+// Columns are counted from 1
+fn error_at(position: usize, message: &str) -> String {
+    format!("column {}: {}", position + 1, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day_16::{part_1, part_2, Transmission};
+
+    #[test]
+    fn compile_works() {
+        let hex = compile("sum(1, product(2, 3), min(7, 8), lt(5, 15))").unwrap();
+
+        assert_eq!(part_2(Transmission::hex(&hex)), 15);
+        assert_eq!(part_1(Transmission::hex(&hex)), 0);
+    }
+
+    #[test]
+    fn versions_work() {
+        assert_eq!(compile("sum@6(1@6, 2@2)").unwrap(), "C200B40A82");
+        assert_eq!(part_1(Transmission::hex(&compile("max@1(gt @2(3@3, 4), eq(5@4, 5))").unwrap())), 10);
+    }
+
+    #[test]
+    fn whitespace_is_ignored() {
+        assert_eq!(compile(" sum( 1 ,\n2 ) ").unwrap(), compile("sum(1,2)").unwrap());
+    }
+
+    #[test]
+    fn invalid_expressions_are_rejected() {
+        assert_eq!(compile("sum(1, 2").unwrap_err(), "column 9: expected ')', found end of expression");
+        assert_eq!(compile("sum(1, xor(2, 3))").unwrap_err(), "column 8: unknown operator xor");
+        assert_eq!(compile("gt(1, 2, 3)").unwrap_err(), "column 1: gt needs exactly 2 operands, got 3");
+        assert_eq!(compile("sum(min())").unwrap_err(), "column 5: min needs at least 1 operand");
+        assert_eq!(compile("sum@8(1)").unwrap_err(), "column 5: expected a version between 0 and 7");
+        assert_eq!(compile("1 2").unwrap_err(), "column 3: expected end of expression");
+        assert_eq!(compile("sum(1, -2)").unwrap_err(), "column 8: expected a number or an operator, found '-'");
+        assert_eq!(compile("340282366920938463463374607431768211456").unwrap_err(), "column 1: literal 340282366920938463463374607431768211456 is too large");
+    }
+}
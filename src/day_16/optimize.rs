@@ -0,0 +1,178 @@
+use super::encoder::{encoded_len, MAX_SUBPACKET_COUNT};
+use super::registry::Registry;
+use super::{evaluate, LiteralPacket, Operator, OperatorPacket, Packet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Passes {
+    // Replace operators on literals by their value, unless evaluating them fails
+    pub fold: bool,
+    // Merge sums into sums and products into products
+    pub flatten: bool,
+    // Replace sums, products, minimums and maximums of a single operand by the operand
+    pub collapse: bool,
+    // Use the shorter length type for every operator
+    pub length_types: bool,
+}
+
+impl Passes {
+    pub fn all() -> Passes {
+        Passes { fold: true, flatten: true, collapse: true, length_types: true }
+    }
+
+    // A comma separated list such as `flatten,collapse`
+    pub fn parse(list: &str) -> Result<Passes, String> {
+        let mut passes: Passes = Passes { fold: false, flatten: false, collapse: false, length_types: false };
+        for pass in list.split(',') {
+            match pass.trim() {
+                "fold" => passes.fold = true,
+                "flatten" => passes.flatten = true,
+                "collapse" => passes.collapse = true,
+                "length" => passes.length_types = true,
+                _ => return Err(format!("Unknown optimization pass {}", pass)),
+            }
+        }
+        Ok(passes)
+    }
+}
+
+// Rewrites the tree bottom-up, from an explicit stack. Versions of removed packets are lost,
+// and the offsets and spans of rewritten packets no longer match any transmission. Constants are
+// folded with the operators of `registry`, the other passes leave overridden operators alone.
+pub fn optimize(packet: Packet, passes: &Passes, registry: &Registry) -> Packet {
+    // Operators whose sub-packets are being optimized, with the ones still to do in reverse
+    let mut stack: Vec<(OperatorPacket, Vec<Packet>)> = vec![];
+    let mut next: Packet = packet;
+
+    loop {
+        let mut done: Packet = match next {
+            Packet::Literal(p) => Packet::Literal(p),
+            Packet::Operator(mut p) => {
+                let mut pending: Vec<Packet> = std::mem::take(&mut p.subpackets);
+                pending.reverse();
+                stack.push((p, pending));
+
+                match stack.last_mut().unwrap().1.pop() {
+                    Some(subpacket) => {
+                        next = subpacket;
+                        continue;
+                    }
+                    None => simplify(stack.pop().unwrap().0, passes, registry),
+                }
+            }
+        };
+
+        // Hand the finished packet to its parent, finishing the parent too if it was the last one
+        loop {
+            let Some((parent, pending)) = stack.last_mut() else {
+                return done;
+            };
+            parent.subpackets.push(done);
+
+            match pending.pop() {
+                Some(subpacket) => {
+                    next = subpacket;
+                    break;
+                }
+                None => done = simplify(stack.pop().unwrap().0, passes, registry),
+            }
+        }
+    }
+}
+
+// Applies the passes to an operator whose sub-packets are already optimized
+fn simplify(mut p: OperatorPacket, passes: &Passes, registry: &Registry) -> Packet {
+    let builtin: bool = registry.is_builtin(p.operator);
+
+    if passes.flatten && builtin && matches!(p.operator, Operator::Sum | Operator::Product) {
+        let mut subpackets: Vec<Packet> = vec![];
+        for subpacket in std::mem::take(&mut p.subpackets) {
+            match subpacket {
+                Packet::Operator(mut inner) if inner.operator == p.operator => subpackets.append(&mut inner.subpackets),
+                subpacket => subpackets.push(subpacket),
+            }
+        }
+        p.subpackets = subpackets;
+    }
+
+    if passes.collapse && builtin && !p.operator.is_comparison() && p.subpackets.len() == 1 {
+        return p.subpackets.pop().unwrap();
+    }
+
+    let offset: usize = p.offset;
+    let version: u8 = p.version;
+    let constant: bool = p.subpackets.iter().all(|subpacket| matches!(subpacket, Packet::Literal(_)));
+
+    if passes.length_types {
+        p.length_type_id = p.subpackets.len() <= MAX_SUBPACKET_COUNT;
+    }
+    p.length_field = match p.length_type_id {
+        true => p.subpackets.len(),
+        false => p.subpackets.iter().map(encoded_len).sum(),
+    };
+
+    let packet: Packet = Packet::Operator(p);
+    if passes.fold && constant {
+        if let Ok(value) = evaluate(&packet, registry) {
+            return Packet::Literal(LiteralPacket { version, type_id: 4, value, offset, span: 0 });
+        }
+    }
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day_16::encoder::encode;
+    use crate::day_16::{compile, decode};
+
+    fn optimize_hex(hex: &str, passes: &str) -> String {
+        encode(&optimize(decode(hex).unwrap(), &Passes::parse(passes).unwrap(), Registry::standard())).unwrap()
+    }
+
+    fn optimize_source(source: &str, passes: &str) -> String {
+        optimize_hex(&compile(source).unwrap(), passes)
+    }
+
+    #[test]
+    fn fold_works() {
+        assert_eq!(optimize_hex("C200B40A82", "fold"), "D060");
+        assert_eq!(optimize_source("sum(1, product@2(2, 3), lt(5, 15))", "fold"), compile("8").unwrap());
+    }
+
+    #[test]
+    fn overflow_is_not_folded() {
+        let source = "sum(340282366920938463463374607431768211455, 1)";
+
+        assert_eq!(optimize_source(source, "fold"), compile(source).unwrap());
+    }
+
+    #[test]
+    fn flatten_works() {
+        assert_eq!(
+            optimize_source("sum(1, sum(2, sum(3, 4)), product(5, product(6, 7)), min(sum(8, 9)))", "flatten"),
+            compile("sum(1, 2, 3, 4, product(5, 6, 7), min(sum(8, 9)))").unwrap()
+        );
+    }
+
+    #[test]
+    fn collapse_works() {
+        assert_eq!(optimize_source("sum(min(max(7)), product(gt(sum(2), 1)))", "collapse"), compile("sum(7, gt(2, 1))").unwrap());
+    }
+
+    #[test]
+    fn length_types_work() {
+        // The puzzle example uses length type 0 for the `lt`
+        assert_eq!(optimize_hex("38006F45291200", "length"), compile("lt@1(10@6, 20@2)").unwrap());
+    }
+
+    #[test]
+    fn all_passes_keep_the_value() {
+        for hex in ["C200B40A82", "04005AC33890", "880086C3E88112", "CE00C43D881120", "D8005AC2A8F0", "9C0141080250320F1802104A08"] {
+            let optimized = optimize(decode(hex).unwrap(), &Passes::all(), Registry::standard());
+
+            assert!(matches!(optimized, Packet::Literal(_)), "input {}", hex);
+            assert_eq!(evaluate(&optimized, Registry::standard()), evaluate(&decode(hex).unwrap(), Registry::standard()), "input {}", hex);
+            assert!(encode(&optimized).unwrap().len() <= hex.len(), "input {}", hex);
+        }
+    }
+}
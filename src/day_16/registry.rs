@@ -0,0 +1,167 @@
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+use super::Operator;
+
+// How many operands an operator takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, operands: usize) -> bool {
+        match *self {
+            Arity::Exactly(n) => operands == n,
+            Arity::AtLeast(n) => operands >= n,
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (bound, n): (&str, usize) = match *self {
+            Arity::Exactly(n) => ("exactly", n),
+            Arity::AtLeast(n) => ("at least", n),
+        };
+        write!(f, "{} {} operand{}", bound, n, if n == 1 { "" } else { "s" })
+    }
+}
+
+// Why an operator has no value for its operands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvalFault {
+    // The value doesn't fit into 128 bits
+    Overflow,
+    // The value doesn't exist, such as the minimum of no values or a remainder of a division
+    // by zero
+    Undefined,
+}
+
+pub type Eval = Box<dyn Fn(&[u128]) -> Result<u128, EvalFault> + Send + Sync>;
+
+pub struct Definition {
+    pub name: &'static str,
+    pub arity: Arity,
+    pub eval: Eval,
+}
+
+// What the operator type IDs mean when decoding and evaluating. Every ID but 4 starts out as a
+// built-in operator, and as the type ID only has 3 bits, a custom operator takes over the ID
+// of a built-in one. Decoded packets carry the name their type ID had in the registry they
+// were decoded with, and everything that shows them uses that name; only the expression
+// language, which writes packets, knows just the built-in names.
+pub struct Registry {
+    operators: [Option<Definition>; 8],
+    overridden: [bool; 8],
+}
+
+impl Registry {
+    pub fn builtin() -> Registry {
+        let mut registry: Registry = Registry { operators: std::array::from_fn(|_| None), overridden: [false; 8] };
+
+        for operator in Operator::ALL {
+            let (arity, eval): (Arity, Eval) = match operator {
+                Operator::Sum => (
+                    Arity::AtLeast(1),
+                    Box::new(|values| values.iter().try_fold(0, |acc: u128, v| acc.checked_add(*v)).ok_or(EvalFault::Overflow)),
+                ),
+                Operator::Product => (
+                    Arity::AtLeast(1),
+                    Box::new(|values| values.iter().try_fold(1, |acc: u128, v| acc.checked_mul(*v)).ok_or(EvalFault::Overflow)),
+                ),
+                Operator::Min => (Arity::AtLeast(1), Box::new(|values| values.iter().min().copied().ok_or(EvalFault::Undefined))),
+                Operator::Max => (Arity::AtLeast(1), Box::new(|values| values.iter().max().copied().ok_or(EvalFault::Undefined))),
+                Operator::Gt => (Arity::Exactly(2), Box::new(|values| Ok((values[0] > values[1]) as u128))),
+                Operator::Lt => (Arity::Exactly(2), Box::new(|values| Ok((values[0] < values[1]) as u128))),
+                Operator::Eq => (Arity::Exactly(2), Box::new(|values| Ok((values[0] == values[1]) as u128))),
+            };
+            registry.register(operator.type_id(), operator.name(), arity, eval).unwrap();
+        }
+        registry
+    }
+
+    // The built-in operators, shared by everything that doesn't ask for others
+    pub fn standard() -> &'static Arc<Registry> {
+        static STANDARD: OnceLock<Arc<Registry>> = OnceLock::new();
+        STANDARD.get_or_init(|| Arc::new(Registry::builtin()))
+    }
+
+    // Defines the operator with `type_id`, replacing the current one. `eval` is only called
+    // with a number of operands `arity` accepts.
+    pub fn register(&mut self, type_id: u8, name: &'static str, arity: Arity, eval: Eval) -> Result<(), String> {
+        match type_id {
+            4 => return Err("type ID 4 is reserved for literals".to_string()),
+            8.. => return Err(format!("type ID {} doesn't fit into 3 bits", type_id)),
+            _ => {}
+        }
+
+        let slot: &mut Option<Definition> = &mut self.operators[type_id as usize];
+        self.overridden[type_id as usize] = slot.is_some();
+        *slot = Some(Definition { name, arity, eval });
+        Ok(())
+    }
+
+    pub fn get(&self, operator: Operator) -> &Definition {
+        self.operators[operator.type_id() as usize].as_ref().unwrap()
+    }
+
+    // Whether `operator` still has its built-in meaning, which the optimizer relies on
+    pub fn is_builtin(&self, operator: Operator) -> bool {
+        !self.overridden[operator.type_id() as usize]
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry::builtin()
+    }
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<(usize, &str)> = self
+            .operators
+            .iter()
+            .enumerate()
+            .filter_map(|(type_id, definition)| definition.as_ref().map(|d| (type_id, d.name)))
+            .collect();
+        f.debug_struct("Registry").field("operators", &names).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_operators_are_registered() {
+        let registry = Registry::builtin();
+
+        assert_eq!(registry.get(Operator::Lt).name, "lt");
+        assert_eq!(registry.get(Operator::Lt).arity, Arity::Exactly(2));
+        assert_eq!((registry.get(Operator::Product).eval)(&[2, 3, 7]), Ok(42));
+        assert_eq!((registry.get(Operator::Sum).eval)(&[u128::MAX, 1]), Err(EvalFault::Overflow));
+        assert_eq!((registry.get(Operator::Min).eval)(&[]), Err(EvalFault::Undefined));
+        assert!(registry.is_builtin(Operator::Eq));
+    }
+
+    #[test]
+    fn register_works() {
+        let mut registry = Registry::builtin();
+
+        registry.register(5, "xor", Arity::AtLeast(1), Box::new(|values| Ok(values.iter().fold(0, |acc, v| acc ^ v)))).unwrap();
+
+        assert_eq!(registry.get(Operator::Gt).name, "xor");
+        assert_eq!((registry.get(Operator::Gt).eval)(&[0b1100, 0b1010, 1]), Ok(0b0111));
+        assert!(!registry.is_builtin(Operator::Gt));
+        assert_eq!(
+            registry.register(4, "xor", Arity::Exactly(2), Box::new(|_| Ok(0))),
+            Err("type ID 4 is reserved for literals".to_string())
+        );
+        assert_eq!(registry.register(8, "xor", Arity::Exactly(2), Box::new(|_| Ok(0))), Err("type ID 8 doesn't fit into 3 bits".to_string()));
+        assert_eq!(Arity::AtLeast(1).to_string(), "at least 1 operand");
+        assert_eq!(Arity::Exactly(2).to_string(), "exactly 2 operands");
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+
+use super::visit::{walk, Visitor};
+use super::Packet;
+
+// Packet counts by type, nesting depth and version histogram of a transmission
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub types: BTreeMap<&'static str, usize>,
+    pub max_depth: usize,
+    pub versions: [usize; 8],
+}
+
+// Operators are counted under their names in the registry they were decoded with
+struct Census {
+    stats: Stats,
+}
+
+impl Visitor for Census {
+    type Output = ();
+    type Error = Infallible;
+
+    fn enter(&mut self, packet: &Packet, depth: usize) -> Result<(), Infallible> {
+        let name: &'static str = match packet {
+            Packet::Literal(_) => "literal",
+            Packet::Operator(p) => p.name(),
+        };
+        *self.stats.types.entry(name).or_insert(0) += 1;
+        self.stats.max_depth = self.stats.max_depth.max(depth);
+        self.stats.versions[packet.version() as usize] += 1;
+        Ok(())
+    }
+
+    fn leave(&mut self, _packet: &Packet, _depth: usize, _outputs: Vec<()>) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+pub fn stats(packet: &Packet) -> Stats {
+    let mut census: Census = Census { stats: Stats::default() };
+    let Ok(()) = walk(packet, &mut census);
+    census.stats
+}
+
+impl Stats {
+    pub fn report(&self) -> String {
+        let mut out: String = format!("packets    {}\n", self.types.values().sum::<usize>());
+        for (name, count) in &self.types {
+            out.push_str(&format!("  {:<9}{}\n", name, count));
+        }
+        out.push_str(&format!("max depth  {}\n", self.max_depth));
+
+        let versions: Vec<String> = (0..8)
+            .filter(|&v| self.versions[v] > 0)
+            .map(|v| format!("{}:{}", v, self.versions[v]))
+            .collect();
+        out.push_str(&format!("versions   {}\n", versions.join(" ")));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day_16::decode;
+
+    #[test]
+    fn stats_works() {
+        let result = stats(&decode("8A004A801A8002F478").unwrap());
+
+        assert_eq!(result, Stats {
+            types: BTreeMap::from([("literal", 1), ("min", 3)]),
+            max_depth: 3,
+            versions: [0, 1, 0, 0, 1, 1, 1, 0],
+        });
+        assert_eq!(result.report(), "\
+packets    4
+  literal  1
+  min      3
+max depth  3
+versions   1:1 4:1 5:1 6:1
+");
+    }
+}
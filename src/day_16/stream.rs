@@ -0,0 +1,308 @@
+use std::io::{BufRead, BufReader, Bytes, Read};
+use std::sync::Arc;
+
+use super::bits::BitSource;
+use super::registry::Registry;
+use super::{base64_digit, parse_packet, BitsError, DecodeOptions, Encoding, Packet};
+
+// Decodes one transmission after another from `source`, reading only as much as the current
+// packet needs. Hex and base64 transmissions are separated by whitespace, binary ones start at
+// the byte after the previous one ends. Bit offsets in packets and errors are relative to the
+// transmission, positions of invalid characters are byte offsets in `source`.
+pub(super) struct PacketStream<R: Read> {
+    bytes: Bytes<BufReader<R>>,
+    options: DecodeOptions,
+    // Bytes read from `source` so far, for error positions
+    position: usize,
+    peeked: Option<u8>,
+    // Whether the current base64 transmission has reached its `=` padding
+    padded: bool,
+    // Why `Encoding::Auto` couldn't be resolved, returned instead of the first packet
+    undetected: Option<BitsError>,
+    done: bool,
+}
+
+impl<R: Read> PacketStream<R> {
+    // `Encoding::Auto` is detected from the start of `source`, as far as it is buffered
+    pub fn new(source: R, mut options: DecodeOptions) -> Self {
+        let mut reader: BufReader<R> = BufReader::new(source);
+        let mut undetected: Option<BitsError> = None;
+        if options.encoding == Encoding::Auto {
+            // A read error shows up again on the first packet
+            options.encoding = Encoding::detect(reader.fill_buf().unwrap_or(&[])).unwrap_or_else(|e| {
+                undetected = Some(e);
+                Encoding::Hex
+            });
+        }
+        PacketStream { bytes: reader.bytes(), options, position: 0, peeked: None, padded: false, undetected, done: false }
+    }
+
+    fn next_byte(&mut self) -> Result<Option<u8>, BitsError> {
+        if let Some(byte) = self.peeked.take() {
+            return Ok(Some(byte));
+        }
+        match self.bytes.next() {
+            Some(Ok(byte)) => {
+                self.position += 1;
+                Ok(Some(byte))
+            }
+            Some(Err(e)) => Err(BitsError::Io(e.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    // The next 4 (hex), 6 (base64) or 8 (binary) bits of the current transmission, `None` at its end
+    fn next_unit(&mut self) -> Result<Option<(u8, usize)>, BitsError> {
+        let (digit, bits): (fn(u8) -> Option<u8>, usize) = match self.options.encoding {
+            Encoding::Binary => return Ok(self.next_byte()?.map(|byte| (byte, 8))),
+            Encoding::Hex | Encoding::Auto => (|byte| (byte as char).to_digit(16).map(|d| d as u8), 4),
+            Encoding::Base64 => (base64_digit, 6),
+        };
+
+        loop {
+            let byte: u8 = match self.next_byte()? {
+                Some(byte) if !byte.is_ascii_whitespace() => byte,
+                _ => {
+                    self.padded = false;
+                    return Ok(None);
+                }
+            };
+            let invalid: BitsError = BitsError::InvalidCharacter { character: byte as char, position: self.position - 1 };
+
+            match digit(byte) {
+                // Strictly, only more padding may follow padding
+                Some(_) if self.padded && self.options.strict => return Err(invalid),
+                Some(d) => return Ok(Some((d, bits))),
+                // Base64 padding only marks the end of the data, the bits before it are zero
+                None if byte == b'=' && bits == 6 => self.padded = true,
+                None if self.options.strict => return Err(invalid),
+                None => {}
+            }
+        }
+    }
+
+    // Skips whitespace between text transmissions, `false` once the source is exhausted
+    fn has_next(&mut self) -> Result<bool, BitsError> {
+        loop {
+            match self.next_byte()? {
+                None => return Ok(false),
+                Some(byte) if self.options.encoding != Encoding::Binary && byte.is_ascii_whitespace() => {}
+                Some(byte) => {
+                    self.peeked = Some(byte);
+                    return Ok(true);
+                }
+            }
+        }
+    }
+
+    fn next_packet(&mut self) -> Result<Option<Packet>, BitsError> {
+        if let Some(e) = self.undetected.take() {
+            return Err(e);
+        }
+        if !self.has_next()? {
+            return Ok(None);
+        }
+
+        let registry: Arc<Registry> = Arc::clone(&self.options.registry);
+        let mut bits: TransmissionBits<R> = TransmissionBits { stream: self, unit: 0, unit_bits: 0, position: 0, error: None };
+        let packet: Result<Packet, BitsError> = parse_packet(&mut bits, &registry).map(|(packet, _)| packet);
+        if let Some(e) = bits.error.take() {
+            return Err(e);
+        }
+        let packet: Packet = packet?;
+
+        // The rest of the current unit is padding, and for text the rest of the transmission
+        let strict: bool = bits.stream.options.strict;
+        let text: bool = bits.stream.options.encoding != Encoding::Binary;
+        loop {
+            let position: usize = bits.position;
+            if bits.unit_bits == 0 && !text {
+                break;
+            }
+            match bits.read_bit() {
+                Some(true) if strict => return Err(BitsError::NonZeroPadding { position }),
+                Some(_) => {}
+                None => break,
+            }
+        }
+        if let Some(e) = bits.error {
+            return Err(e);
+        }
+
+        Ok(Some(packet))
+    }
+}
+
+impl<R: Read> Iterator for PacketStream<R> {
+    type Item = Result<Packet, BitsError>;
+
+    // Stops after the first error, as the rest of the source can't be aligned anymore
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result: Result<Option<Packet>, BitsError> = self.next_packet();
+        if !matches!(result, Ok(Some(_))) {
+            self.done = true;
+        }
+        result.transpose()
+    }
+}
+
+// The bits of the transmission at the front of a `PacketStream`
+struct TransmissionBits<'a, R: Read> {
+    stream: &'a mut PacketStream<R>,
+    unit: u8,
+    unit_bits: usize,
+    position: usize,
+    // Set when reading fails for another reason than the end of the transmission
+    error: Option<BitsError>,
+}
+
+impl<R: Read> BitSource for TransmissionBits<'_, R> {
+    fn position(&self) -> usize {
+        self.position
+    }
+
+    fn read_bits(&mut self, n: usize) -> Option<u64> {
+        let mut value: u64 = 0;
+        let mut left: usize = n;
+
+        while left > 0 {
+            if self.unit_bits == 0 {
+                match self.stream.next_unit() {
+                    Ok(Some((unit, unit_bits))) => (self.unit, self.unit_bits) = (unit, unit_bits),
+                    Ok(None) => return None,
+                    Err(e) => {
+                        self.error = Some(e);
+                        return None;
+                    }
+                }
+            }
+
+            let take: usize = left.min(self.unit_bits);
+            let bits: u8 = (self.unit >> (self.unit_bits - take)) & (0xFF >> (8 - take));
+            value = (value << take) | bits as u64;
+
+            self.unit_bits -= take;
+            self.position += take;
+            left -= take;
+        }
+
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day_16::bits::hex_to_bytes;
+    use crate::day_16::decode;
+
+    fn options(strict: bool, encoding: Encoding) -> DecodeOptions {
+        DecodeOptions { strict, encoding, ..DecodeOptions::default() }
+    }
+
+    fn transmission_bits<'a, 'b>(stream: &'a mut PacketStream<&'b [u8]>) -> TransmissionBits<'a, &'b [u8]> {
+        TransmissionBits { stream, unit: 0, unit_bits: 0, position: 0, error: None }
+    }
+
+    #[test]
+    fn read_bits_works() {
+        let mut stream = PacketStream::new("D2FE28".as_bytes(), options(true, Encoding::Hex));
+        let mut reader = transmission_bits(&mut stream);
+
+        assert_eq!(reader.read_bits(3), Some(6));
+        assert_eq!(reader.read_bits(3), Some(4));
+        assert_eq!(reader.read_bit(), Some(true));
+        assert_eq!(reader.read_bits(4), Some(0b0111));
+        assert_eq!(reader.position(), 11);
+        assert_eq!(reader.read_bits(13), Some(0b1_1110_0010_1000));
+        assert_eq!(reader.read_bit(), None);
+        assert_eq!(reader.error, None);
+    }
+
+    #[test]
+    fn read_bits_across_many_units_works() {
+        let bytes = [0x0F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xF0];
+        let mut stream = PacketStream::new(bytes.as_slice(), options(true, Encoding::Binary));
+        let mut reader = transmission_bits(&mut stream);
+
+        assert_eq!(reader.read_bits(4), Some(0));
+        assert_eq!(reader.read_bits(64), Some(u64::MAX));
+        assert_eq!(reader.read_bits(4), Some(0));
+        assert_eq!(reader.read_bit(), None);
+    }
+
+    #[test]
+    fn hex_stream_works() {
+        let input = "D2FE28\n38006F45291200 EE00D40C823060\n\n8A004A801A8002F478\n";
+
+        let packets: Vec<Packet> = PacketStream::new(input.as_bytes(), options(true, Encoding::Hex)).map(Result::unwrap).collect();
+
+        assert_eq!(packets, input.split_whitespace().map(|hex| decode(hex).unwrap()).collect::<Vec<Packet>>());
+        assert_eq!(packets[2].offset(), 0);
+    }
+
+    #[test]
+    fn binary_stream_works() {
+        let hex = ["D2FE28", "38006F45291200", "9C0141080250320F1802104A08"];
+        let bytes: Vec<u8> = hex.iter().flat_map(|h| hex_to_bytes(h)).collect();
+
+        let packets: Vec<Packet> = PacketStream::new(bytes.as_slice(), options(false, Encoding::Binary))
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(packets, hex.iter().map(|h| decode(h).unwrap()).collect::<Vec<Packet>>());
+    }
+
+    #[test]
+    fn base64_stream_works() {
+        let hex = ["D2FE28", "38006F45291200", "9C0141080250320F1802104A08"];
+
+        let packets: Vec<Packet> = PacketStream::new("0v4o OABvRSkSAA==\nnAFBCAJQMg8YAhBKCA==\n".as_bytes(), options(true, Encoding::Base64))
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(packets, hex.iter().map(|h| decode(h).unwrap()).collect::<Vec<Packet>>());
+    }
+
+    #[test]
+    fn encoding_is_detected() {
+        assert_eq!(Encoding::detect(b"D2FE28\n38006F45291200\n"), Ok(Encoding::Hex));
+        assert_eq!(Encoding::detect(b"0v4o OABvRSkSAA==\n"), Ok(Encoding::Base64));
+        assert_eq!(Encoding::detect(&hex_to_bytes("D2FE28")), Ok(Encoding::Binary));
+        assert_eq!(PacketStream::new(hex_to_bytes("D2FE28").as_slice(), options(true, Encoding::Auto)).next(), Some(decode("D2FE28")));
+        assert_eq!(PacketStream::new("igBKgBqAAvR4".as_bytes(), options(true, Encoding::Auto)).next(), Some(decode("8A004A801A8002F478")));
+    }
+
+    #[test]
+    fn errors_end_the_stream() {
+        let mut stream = PacketStream::new("D2FE28 D2FE29 D2FE28".as_bytes(), options(true, Encoding::Hex));
+
+        assert!(stream.next().unwrap().is_ok());
+        assert_eq!(stream.next(), Some(Err(BitsError::NonZeroPadding { position: 23 })));
+        assert_eq!(stream.next(), None);
+
+        let mut stream = PacketStream::new("D2FE28 D2GE28".as_bytes(), options(true, Encoding::Hex));
+
+        assert!(stream.next().unwrap().is_ok());
+        assert_eq!(stream.next(), Some(Err(BitsError::InvalidCharacter { character: 'G', position: 9 })));
+
+        let mut stream = PacketStream::new("0v4o=".as_bytes(), options(true, Encoding::Hex));
+
+        assert_eq!(stream.next(), Some(Err(BitsError::InvalidCharacter { character: 'v', position: 1 })));
+
+        let mut stream = PacketStream::new("0v4o== 0v=4o".as_bytes(), options(true, Encoding::Base64));
+
+        assert!(stream.next().unwrap().is_ok());
+        assert_eq!(stream.next(), Some(Err(BitsError::InvalidCharacter { character: '4', position: 10 })));
+        assert_eq!(PacketStream::new("0v=4o".as_bytes(), options(false, Encoding::Base64)).next(), Some(decode("D2FE28")));
+
+        let mut stream = PacketStream::new("D2F 38006F".as_bytes(), options(false, Encoding::Hex));
+
+        assert_eq!(stream.next(), Some(Err(BitsError::Truncated { offset: 0 })));
+        assert_eq!(stream.next(), None);
+    }
+}
@@ -0,0 +1,151 @@
+use std::convert::Infallible;
+
+use super::registry::Registry;
+use super::visit::{walk, Visitor};
+use super::{BitsError, Evaluator, OperatorPacket, Packet};
+
+// Records every operator with the values of its operands as it is evaluated
+struct Trace<'a> {
+    registry: &'a Registry,
+    lines: Vec<String>,
+}
+
+impl Visitor for Trace<'_> {
+    type Output = u128;
+    type Error = BitsError;
+
+    fn leave(&mut self, packet: &Packet, depth: usize, values: Vec<u128>) -> Result<u128, BitsError> {
+        let p: &OperatorPacket = match packet {
+            Packet::Literal(p) => return Ok(p.value()),
+            Packet::Operator(p) => p,
+        };
+        let call: String = format!(
+            "{}({})",
+            p.name(),
+            values.iter().map(u128::to_string).collect::<Vec<String>>().join(", ")
+        );
+
+        match (Evaluator { registry: self.registry }).leave(packet, depth, values) {
+            Ok(value) => {
+                self.lines.push(format!("{:>6}  {} = {}", packet.offset(), call, value));
+                Ok(value)
+            }
+            Err(e) => {
+                self.lines.push(format!("{:>6}  {} fails: {}", packet.offset(), call, e));
+                Err(e)
+            }
+        }
+    }
+}
+
+// Writes the whole expression into one string in a single pass, so deep trees take linear time
+struct Expression {
+    out: String,
+    // Whether the operators on the current path have written an operand yet
+    open: Vec<bool>,
+}
+
+impl Visitor for Expression {
+    type Output = ();
+    type Error = Infallible;
+
+    fn enter(&mut self, packet: &Packet, _depth: usize) -> Result<(), Infallible> {
+        if let Some(written) = self.open.last_mut() {
+            if *written {
+                self.out.push_str(", ");
+            }
+            *written = true;
+        }
+
+        match packet {
+            Packet::Literal(p) => self.out.push_str(&p.value().to_string()),
+            Packet::Operator(p) => {
+                self.out.push_str(p.name());
+                self.out.push('(');
+                self.open.push(false);
+            }
+        }
+        Ok(())
+    }
+
+    fn leave(&mut self, packet: &Packet, _depth: usize, _operands: Vec<()>) -> Result<(), Infallible> {
+        if let Packet::Operator(_) = packet {
+            self.open.pop();
+            self.out.push(')');
+        }
+        Ok(())
+    }
+}
+
+// One line per operator, bottom-up, with its bit offset:
+//     <bit offset>  <name>(<operand values>) = <value>
+// and then the value of the outermost packet with the expression it is derived from. Stops at
+// the first operator that can't be evaluated.
+pub fn trace(packet: &Packet, registry: &Registry) -> String {
+    let mut trace: Trace = Trace { registry, lines: vec![] };
+    let result: Result<u128, BitsError> = walk(packet, &mut trace);
+
+    let mut out: String = trace.lines.iter().map(|line| format!("{}\n", line)).collect();
+    if let Ok(value) = result {
+        let mut expression: Expression = Expression { out: String::new(), open: vec![] };
+        let Ok(()) = walk(packet, &mut expression);
+        out.push_str(&format!("value {} = {}\n", value, expression.out));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day_16::bits::{bytes_to_hex, BitWriter};
+    use crate::day_16::{compile, decode};
+
+    fn trace_source(source: &str) -> String {
+        trace_source_hex(&compile(source).unwrap())
+    }
+
+    fn trace_source_hex(hex: &str) -> String {
+        trace(&decode(hex).unwrap(), Registry::standard())
+    }
+
+    #[test]
+    fn trace_works() {
+        // The first line is indented as well, so it can't follow a line continuation
+        assert_eq!(trace_source("sum(min(7, 8, 9), product(2, lt(1, 3)))"), "    18  min(7, 8, 9) = 7
+    98  lt(1, 3) = 1
+    69  product(2, 1) = 2
+     0  sum(7, 2) = 9
+value 9 = sum(min(7, 8, 9), product(2, lt(1, 3)))
+");
+        assert_eq!(trace_source("42"), "value 42 = 42\n");
+    }
+
+    #[test]
+    fn deep_traces_work() {
+        const DEPTH: usize = 20_000;
+
+        // min(min(...min(7)...))
+        let mut writer = BitWriter::new();
+        for _ in 0..DEPTH {
+            writer.write_bits(0, 3);
+            writer.write_bits(2, 3);
+            writer.write_bit(true);
+            writer.write_bits(1, 11);
+        }
+        writer.write_bits(0, 3);
+        writer.write_bits(4, 3);
+        writer.write_bits(7, 5);
+        let result = trace_source_hex(&bytes_to_hex(&writer.into_bytes()));
+
+        assert_eq!(result.lines().count(), DEPTH + 1);
+        assert!(result.ends_with(&format!("value 7 = {}7{}\n", "min(".repeat(DEPTH), ")".repeat(DEPTH))));
+    }
+
+    #[test]
+    fn trace_stops_at_the_first_error() {
+        assert_eq!(
+            trace_source("max(3, product(340282366920938463463374607431768211455, 2), 4)"),
+            "    29  product(340282366920938463463374607431768211455, 2) fails: value of the packet at bit 29 overflows 128 bits\n"
+        );
+    }
+}
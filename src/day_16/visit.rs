@@ -0,0 +1,90 @@
+use super::Packet;
+
+// An analysis over a packet tree. `walk` calls `enter` for every packet before any of its
+// sub-packets (pre-order) and `leave` after all of them (post-order), with the outputs of the
+// sub-packets in order. The root has depth 0.
+pub trait Visitor {
+    type Output;
+    type Error;
+
+    fn enter(&mut self, _packet: &Packet, _depth: usize) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn leave(&mut self, packet: &Packet, depth: usize, outputs: Vec<Self::Output>) -> Result<Self::Output, Self::Error>;
+}
+
+// Visits the tree from an explicit stack, so the nesting depth is only limited by memory.
+// Stops at the first error.
+pub fn walk<V: Visitor>(packet: &Packet, visitor: &mut V) -> Result<V::Output, V::Error> {
+    // Packets on the current path with the index of their next sub-packet, and the outputs of
+    // the sub-packets finished so far
+    let mut stack: Vec<(&Packet, usize)> = vec![(packet, 0)];
+    let mut outputs: Vec<V::Output> = vec![];
+    visitor.enter(packet, 0)?;
+
+    while !stack.is_empty() {
+        let depth: usize = stack.len() - 1;
+        let (packet, next): &mut (&Packet, usize) = stack.last_mut().unwrap();
+        let packet: &Packet = packet;
+        let subpackets: &[Packet] = match packet {
+            Packet::Literal(_) => &[],
+            Packet::Operator(p) => p.subpackets(),
+        };
+
+        if let Some(subpacket) = subpackets.get(*next) {
+            *next += 1;
+            visitor.enter(subpacket, depth + 1)?;
+            stack.push((subpacket, 0));
+        } else {
+            stack.pop();
+            let operands: Vec<V::Output> = outputs.split_off(outputs.len() - subpackets.len());
+            outputs.push(visitor.leave(packet, depth, operands)?);
+        }
+    }
+
+    Ok(outputs.pop().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day_16::decode;
+    use std::convert::Infallible;
+
+    // Records the order of the hooks
+    struct Trace(Vec<String>);
+
+    impl Visitor for Trace {
+        type Output = usize;
+        type Error = Infallible;
+
+        fn enter(&mut self, packet: &Packet, depth: usize) -> Result<(), Infallible> {
+            self.0.push(format!("enter {} at {}", packet.offset(), depth));
+            Ok(())
+        }
+
+        fn leave(&mut self, packet: &Packet, depth: usize, outputs: Vec<usize>) -> Result<usize, Infallible> {
+            self.0.push(format!("leave {} at {} with {:?}", packet.offset(), depth, outputs));
+            Ok(outputs.iter().sum::<usize>() + 1)
+        }
+    }
+
+    #[test]
+    fn walk_works() {
+        let mut trace = Trace(vec![]);
+
+        // lt(10, 20)
+        let Ok(count) = walk(&decode("38006F45291200").unwrap(), &mut trace);
+
+        assert_eq!(count, 3);
+        assert_eq!(trace.0, vec![
+            "enter 0 at 0",
+            "enter 22 at 1",
+            "leave 22 at 1 with []",
+            "enter 33 at 1",
+            "leave 33 at 1 with []",
+            "leave 0 at 0 with [1, 1]",
+        ]);
+    }
+}
@@ -1,49 +1,134 @@
+// The pair structure as a tree, for debugging reductions. It reads the input on its own, so
+// the tests can use it as a reference, and is benchmarked as the `tree` variant of the day.
+pub(crate) mod tree;
+
 use std::cmp::max;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign};
+use std::str::FromStr;
+
+use crate::visualize::{Color, FrameBuffer, Visualizer};
 
+// The regular numbers from left to right, with the number of pairs around each. The empty
+// number (the default) is not a snailfish number, but adds as nothing, so sums can start with it.
 #[derive(Debug, Clone)]
 struct SnailfishNumber {
-    values: Vec<u16>,
+    values: Vec<u64>,
     depths: Vec<u16>,
 }
 
-pub fn part_1(input: &str) -> u16 {
-
-    let mut result: SnailfishNumber = SnailfishNumber{
-        values: vec![],
-        depths: vec![],
-    };
-
-    let mut first = true;
+impl SnailfishNumber {
+    // Makes `self` the unreduced pair of `self` and `other`
+    fn pair(&mut self, mut other: SnailfishNumber) {
+        self.values.append(&mut other.values);
+        self.depths.append(&mut other.depths);
+        for depth in self.depths.iter_mut() {
+            *depth += 1;
+        }
+    }
 
-    for line in input.lines().into_iter() {
-        let mut other: SnailfishNumber = parse_number(line);
+    // Two regular numbers next to each other at the same depth are a pair, which is replaced by
+    // its magnitude until only one is left. Only unreduced numbers nested far deeper than the
+    // puzzle's can overflow, which is an error.
+    fn magnitude(&self) -> Result<u64, String> {
+        let mut stack: Vec<(u64, u16)> = vec![];
 
-        result.values.append(&mut other.values);
-        result.depths.append(&mut other.depths);
+        for (&value, &depth) in self.values.iter().zip(self.depths.iter()) {
+            stack.push((value, depth));
 
-        if !first {
-            for i in 0..result.depths.len() {
-                result.depths[i] += 1;
+            while stack.len() >= 2 && stack[stack.len() - 1].1 == stack[stack.len() - 2].1 {
+                let (right, depth): (u64, u16) = stack.pop().unwrap();
+                let (left, _): (u64, u16) = stack.pop().unwrap();
+                let magnitude: Option<u64> = left.checked_mul(3).and_then(|l| l.checked_add(right.checked_mul(2)?));
+                stack.push((magnitude.ok_or("magnitude overflows 64 bits")?, depth - 1));
             }
-    
-            explode_split(&mut result);
-        } else {
-            first = false;
+        }
+
+        match stack.as_slice() {
+            [(magnitude, 0)] => Ok(*magnitude),
+            _ => Err(format!("not a snailfish number: {}", self)),
         }
     }
+}
+
+impl Add for SnailfishNumber {
+    type Output = SnailfishNumber;
 
-    compute_magnitude(&mut result)
+    fn add(mut self, other: SnailfishNumber) -> SnailfishNumber {
+        self += other;
+        self
+    }
 }
 
-pub fn part_2(input: &str) -> u16 {
+impl AddAssign for SnailfishNumber {
+    fn add_assign(&mut self, other: SnailfishNumber) {
+        self.pair(other);
+        explode_split(self);
+    }
+}
 
-    let mut numbers: Vec<SnailfishNumber> = vec![];
+// Adds the numbers one after the other, starting with the first. There is no snailfish number
+// to sum to without any, so this panics on an empty iterator.
+impl Sum for SnailfishNumber {
+    fn sum<I: Iterator<Item = SnailfishNumber>>(iter: I) -> Self {
+        iter.reduce(|sum, n| sum + n).expect("no snailfish numbers to sum")
+    }
+}
 
-    for line in input.lines().into_iter() {
-        numbers.push(parse_number(line));
+impl<'a> Sum<&'a SnailfishNumber> for SnailfishNumber {
+    fn sum<I: Iterator<Item = &'a SnailfishNumber>>(iter: I) -> Self {
+        iter.cloned().sum()
     }
+}
 
-    let mut largest: u16 = 0;
+impl FromStr for SnailfishNumber {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_number(s)
+    }
+}
+
+// Writes the pairs back around the regular numbers, without whitespace, so formatting a parsed
+// number gives the same text again
+impl fmt::Display for SnailfishNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // For every open pair, whether its left element is complete
+        let mut open: Vec<bool> = vec![];
+
+        for (&value, &depth) in self.values.iter().zip(self.depths.iter()) {
+            while open.len() < depth as usize {
+                write!(f, "[")?;
+                open.push(false);
+            }
+            write!(f, "{}", value)?;
+
+            // Close the pairs this number completes, up to the first one that needs its right element
+            while let Some(left_done) = open.last_mut() {
+                if *left_done {
+                    write!(f, "]")?;
+                    open.pop();
+                } else {
+                    write!(f, ",")?;
+                    *left_done = true;
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn part_1(input: &str) -> u64 {
+    parse_numbers(input).into_iter().sum::<SnailfishNumber>().magnitude().unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn part_2(input: &str) -> u64 {
+
+    let numbers: Vec<SnailfishNumber> = parse_numbers(input);
+
+    let mut largest: u64 = 0;
 
     for i in 0..numbers.len() {
         for j in 0..numbers.len() {
@@ -51,55 +136,161 @@ pub fn part_2(input: &str) -> u16 {
                 continue;
             }
 
-            let mut a = numbers[i].clone();
-            let mut b = numbers[j].clone();
-
-            a.values.append(&mut b.values);
-            a.depths.append(&mut b.depths);
-
-            for i in 0..a.depths.len() {
-                a.depths[i] += 1;
-            }
-    
-            explode_split(&mut a);
-            let magnitude: u16 = compute_magnitude(&mut a);
+            let magnitude: u64 = (numbers[i].clone() + numbers[j].clone()).magnitude().unwrap_or_else(|e| panic!("{}", e));
 
             largest = max(magnitude, largest);
-            
         }
     }
 
     largest
 }
 
+// Shows the flat encoding after every reduction step: each regular number is drawn
+// in the row of its depth, about-to-explode pairs in yellow and about-to-split numbers in red
+pub fn visualize(input: &str, visualizer: &mut Visualizer) {
+    let mut result: Option<SnailfishNumber> = None;
+
+    for (line_id, other) in parse_numbers(input).into_iter().enumerate() {
+
+        let Some(mut sum) = result else {
+            show_number(visualizer, &other, "start");
+            result = Some(other);
+            continue;
+        };
+
+        sum.pair(other);
+        show_number(visualizer, &sum, &format!("add line {}", line_id + 1));
+
+        let mut step: usize = 0;
+        explode_split_with(&mut sum, |n| {
+            step += 1;
+            show_number(visualizer, n, &format!("add line {}, step {}", line_id + 1, step));
+        });
+
+        result = Some(sum);
+    }
+
+    if let Some(sum) = result {
+        let caption: String = match sum.magnitude() {
+            Ok(magnitude) => format!("magnitude {}", magnitude),
+            Err(e) => e,
+        };
+        show_number(visualizer, &sum, &caption);
+    }
+}
+
+fn show_number(visualizer: &mut Visualizer, n: &SnailfishNumber, caption: &str) {
+    const W_VALUE: usize = 3;
+
+    let mut frame: FrameBuffer = FrameBuffer::new(n.values.len() * W_VALUE, 5);
+    for (i, (&value, &depth)) in n.values.iter().zip(n.depths.iter()).enumerate() {
+        let color: Color = if depth > 4 {
+            Color::Yellow
+        } else if value > 9 {
+            Color::Red
+        } else {
+            Color::White
+        };
+        // A bare regular number has depth 0 and goes on the first row with the outermost pair
+        frame.text(i * W_VALUE, (depth as usize).saturating_sub(1), &value.to_string(), Some(color));
+    }
+    visualizer.show(&frame, caption);
+}
+
+// One number per line, blank lines are skipped. Panics on the first malformed number.
+fn parse_numbers(input: &str) -> Vec<SnailfishNumber> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(line_id, line)| line.parse().unwrap_or_else(|e| panic!("line {}: {}", line_id + 1, e)))
+        .collect()
+}
 
-fn parse_number(input: &str) -> SnailfishNumber {
-    // Returns (values, depths)
-    let mut values: Vec<u16> = vec![];
+// Parses `[[1,2],[[3,4],5]]` into the regular numbers with the number of pairs around each.
+// Regular numbers can have several digits, so unreduced numbers can be read as well, and
+// whitespace is allowed between any two tokens. They have to fit into 16 bits, as reducing
+// splits a regular number about as often as it is large.
+fn parse_number(input: &str) -> Result<SnailfishNumber, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut values: Vec<u64> = vec![];
     let mut depths: Vec<u16> = vec![];
-    let mut depth: u16 = 0;
-
-    for c in input.chars() {
-        match c {
-            '[' => depth += 1,
-            ']' => depth -= 1,
-            ',' => continue,
-            _ => {
-                values.push(c.to_digit(10).unwrap() as u16);
-                depths.push(depth);
+
+    // For every open pair, whether its left element is complete
+    let mut open: Vec<bool> = vec![];
+    let mut expect_element: bool = true;
+    let mut i: usize = 0;
+
+    loop {
+        while chars.get(i).is_some_and(|c| c.is_whitespace()) {
+            i += 1;
+        }
+        let c: Option<char> = chars.get(i).copied();
+
+        if expect_element {
+            match c {
+                Some('[') => open.push(false),
+                Some(c) if c.is_ascii_digit() => {
+                    let start: usize = i;
+                    while chars.get(i + 1).is_some_and(char::is_ascii_digit) {
+                        i += 1;
+                    }
+                    let digits: String = chars[start..=i].iter().collect();
+                    let value: u16 = digits.parse().map_err(|_| error_at(start, &format!("regular number {} is too large", digits)))?;
+
+                    values.push(value as u64);
+                    depths.push(open.len() as u16);
+                    expect_element = false;
+                }
+                _ => return Err(error_at(i, &format!("expected '[' or a regular number, found {}", describe(c)))),
+            }
+        } else {
+            match (open.last_mut(), c) {
+                (None, None) => break,
+                (None, Some(c)) => return Err(error_at(i, &format!("expected end of number, found '{}'", c))),
+                (Some(left_done @ false), Some(',')) => {
+                    *left_done = true;
+                    expect_element = true;
+                }
+                (Some(false), c) => return Err(error_at(i, &format!("expected ',', found {}", describe(c)))),
+                (Some(true), Some(']')) => {
+                    open.pop();
+                }
+                (Some(true), c) => return Err(error_at(i, &format!("expected ']', found {}", describe(c)))),
             }
         }
+        i += 1;
+    }
+
+    Ok(SnailfishNumber{values, depths})
+}
+
+fn describe(c: Option<char>) -> String {
+    match c {
+        Some(c) => format!("'{}'", c),
+        None => "end of number".to_string(),
     }
+}
 
-    SnailfishNumber{values, depths}
+// Columns are counted from 1
+fn error_at(position: usize, message: &str) -> String {
+    format!("column {}: {}", position + 1, message)
 }
 
-fn explode_split(n: &mut SnailfishNumber) -> () {
+fn explode_split(n: &mut SnailfishNumber) {
+    explode_split_with(n, |_| {});
+}
+
+// Same as `explode_split`, calling `on_step` after every round of explosions and every split
+fn explode_split_with<F: FnMut(&SnailfishNumber)>(n: &mut SnailfishNumber, mut on_step: F) {
     'outer: loop {
+        let mut exploded: bool = false;
         let mut i: usize = 0;
         while i < n.values.len() {
             // Explode
             if n.depths[i] > 4 {
+                exploded = true;
+
                 // Add first value to the left
                 if i > 0 {
                     n.values[i-1] += n.values[i];
@@ -120,6 +311,10 @@ fn explode_split(n: &mut SnailfishNumber) -> () {
             i += 1;
         }
 
+        if exploded {
+            on_step(n);
+        }
+
         i = 0;
         while i < n.values.len() {
             // Split
@@ -127,14 +322,15 @@ fn explode_split(n: &mut SnailfishNumber) -> () {
                 let original_depth: u16 = n.depths[i];
                 let original_value = n.values[i];
 
-                let l: u16 = original_value >> 1;
-                let r: u16 = l + (original_value & 1);
+                let l: u64 = original_value >> 1;
+                let r: u64 = l + (original_value & 1);
                 n.values[i] = l;
                 n.values.insert(i+1, r);
 
                 n.depths[i] = original_depth + 1;
                 n.depths.insert(i+1, original_depth + 1);
-    
+
+                on_step(n);
                 continue 'outer;
             }
             i += 1;
@@ -144,23 +340,191 @@ fn explode_split(n: &mut SnailfishNumber) -> () {
     }
 }
 
-fn compute_magnitude(n: &mut SnailfishNumber) -> u16 {
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Rng;
 
-    for depth in (1..=4).rev() {
-        let mut i: usize = 0;
-        while i < n.values.len() {
-            if n.depths[i] == depth {
+    #[test]
+    fn part_1_works() {
+        let result = part_1(&data());
 
-                n.values[i] = 3 * n.values[i] + 2 * n.values[i+1];
-                n.depths[i] -= 1;
+        assert_eq!(result, 4140);
+    }
 
-                n.values.remove(i+1);
-                n.depths.remove(i+1);
-            }
-            i += 1;
+    #[test]
+    fn part_2_works() {
+        let result = part_2(&data());
+
+        assert_eq!(result, 3993);
+    }
+
+    #[test]
+    fn parse_number_works() {
+        let n = parse_number(" [[1, 2],\t[[30,4] , 5]]").unwrap();
+
+        assert_eq!(n.values, vec![1, 2, 30, 4, 5]);
+        assert_eq!(n.depths, vec![2, 2, 3, 3, 2]);
+        assert_eq!(parse_number("7").unwrap().depths, vec![0]);
+    }
+
+    #[test]
+    fn display_works() {
+        for line in data().lines() {
+            assert_eq!(line.parse::<SnailfishNumber>().unwrap().to_string(), line);
         }
+        assert_eq!(" [ 10 ,[2, 3]]".parse::<SnailfishNumber>().unwrap().to_string(), "[10,[2,3]]");
+        assert_eq!("7".parse::<SnailfishNumber>().unwrap().to_string(), "7");
+
+        let mut n: SnailfishNumber = "[[[[[9,8],1],2],3],4]".parse().unwrap();
+        explode_split(&mut n);
+
+        assert_eq!(n.to_string(), "[[[[0,9],2],3],4]");
+    }
+
+    #[test]
+    fn arithmetic_works() {
+        let a: SnailfishNumber = "[[[[4,3],4],4],[7,[[8,4],9]]]".parse().unwrap();
+        let b: SnailfishNumber = "[1,1]".parse().unwrap();
+
+        assert_eq!((a.clone() + b.clone()).to_string(), "[[[[0,7],4],[[7,8],[6,0]]],[8,1]]");
+
+        let mut c = a.clone();
+        c += b;
+
+        assert_eq!(c.to_string(), "[[[[0,7],4],[[7,8],[6,0]]],[8,1]]");
+        assert_eq!(a.to_string(), "[[[[4,3],4],4],[7,[[8,4],9]]]");
+
+        let numbers: Vec<SnailfishNumber> = parse_numbers(&data());
+        let sum: SnailfishNumber = numbers.iter().sum();
+
+        assert_eq!(sum.to_string(), "[[[[6,6],[7,6]],[[7,7],[7,0]]],[[[7,7],[7,7]],[[7,8],[9,9]]]]");
+        assert_eq!(sum.magnitude(), Ok(4140));
+        assert_eq!(numbers.into_iter().sum::<SnailfishNumber>().to_string(), sum.to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "no snailfish numbers to sum")]
+    fn empty_sum_panics() {
+        Vec::<SnailfishNumber>::new().into_iter().sum::<SnailfishNumber>();
     }
 
-    assert!(n.values.len() == 1);
-    n.values[0]
+    #[test]
+    fn first_number_is_not_reduced_alone() {
+        // Reducing [999,999] before adding [1,1] would split it differently
+        let input = "[999,999]\n[1,1]";
+
+        assert_eq!(part_1(input), 4113);
+        assert_eq!(part_1(input), tree::part_1(input));
+        assert_eq!(part_1("[65535,65535]\n[1,1]"), tree::part_1("[65535,65535]\n[1,1]"));
+    }
+
+    #[test]
+    fn magnitude_works() {
+        let n: SnailfishNumber = "[[1,2],[[3,4],5]]".parse().unwrap();
+
+        assert_eq!(n.magnitude(), Ok(143));
+        assert_eq!(n.to_string(), "[[1,2],[[3,4],5]]");
+        assert_eq!("[[[[8,7],[7,7]],[[8,6],[7,7]]],[[[0,7],[6,6]],[8,7]]]".parse::<SnailfishNumber>().unwrap().magnitude(), Ok(3488));
+        assert_eq!("9".parse::<SnailfishNumber>().unwrap().magnitude(), Ok(9));
+    }
+
+    #[test]
+    fn large_values_work() {
+        assert_eq!("[40000,1]".parse::<SnailfishNumber>().unwrap().magnitude(), Ok(120002));
+        assert_eq!("[65535,65535]".parse::<SnailfishNumber>().unwrap().magnitude(), Ok(5 * 65535));
+
+        // Exploding [1,1] adds to 65535, beyond 16 bits
+        let mut n: SnailfishNumber = "[[[[65535,[1,1]],2],3],4]".parse().unwrap();
+        let mut t = tree::SnailfishTree::from(&n);
+        explode_split(&mut n);
+        t.reduce();
+
+        assert_eq!(tree::SnailfishTree::from(&n), t);
+        assert_eq!(n.magnitude(), t.magnitude());
+    }
+
+    #[test]
+    fn magnitude_overflow_is_an_error() {
+        // 45 pairs deep, unreduced: the magnitude grows by a factor of 3 per pair
+        let input: String = format!("{}1{}", "[".repeat(45), ",1]".repeat(45));
+        let n: SnailfishNumber = input.parse().unwrap();
+
+        assert_eq!(n.magnitude(), Err("magnitude overflows 64 bits".to_string()));
+        assert_eq!(input.parse::<tree::SnailfishTree>().unwrap().magnitude(), Err("magnitude overflows 64 bits".to_string()));
+    }
+
+    #[test]
+    fn malformed_numbers_are_rejected() {
+        assert_eq!(parse_number("[1,2").unwrap_err(), "column 5: expected ']', found end of number");
+        assert_eq!(parse_number("[1,2]]").unwrap_err(), "column 6: expected end of number, found ']'");
+        assert_eq!(parse_number("[1,[2,3]").unwrap_err(), "column 9: expected ']', found end of number");
+        assert_eq!(parse_number("[1 2]").unwrap_err(), "column 4: expected ',', found '2'");
+        assert_eq!(parse_number("[1,2,3]").unwrap_err(), "column 5: expected ']', found ','");
+        assert_eq!(parse_number("[,2]").unwrap_err(), "column 2: expected '[' or a regular number, found ','");
+        assert_eq!(parse_number("[1,x]").unwrap_err(), "column 4: expected '[' or a regular number, found 'x'");
+        assert_eq!(parse_number("").unwrap_err(), "column 1: expected '[' or a regular number, found end of number");
+        assert_eq!(parse_number("[1,65536]").unwrap_err(), "column 4: regular number 65536 is too large");
+    }
+
+    #[test]
+    fn visualize_shows_a_bare_number() {
+        visualize("7", &mut Visualizer::new(false, 1000.));
+    }
+
+    #[test]
+    #[should_panic(expected = "line 2: column 2: expected '[' or a regular number, found 'a'")]
+    fn part_1_reports_the_line() {
+        part_1("[1,2]\n[a,2]");
+    }
+
+    #[test]
+    fn part_1_matches_reference() {
+        let mut rng = Rng::new(18);
+
+        for _ in 0..2000 {
+            let n: usize = 2 + rng.below(9);
+            let input: String = random_input(&mut rng, n);
+
+            assert_eq!(part_1(&input), tree::part_1(&input), "input:\n{}", input);
+        }
+    }
+
+    #[test]
+    fn part_2_matches_reference() {
+        let mut rng = Rng::new(1818);
+
+        for _ in 0..200 {
+            let n: usize = 2 + rng.below(5);
+            let input: String = random_input(&mut rng, n);
+
+            assert_eq!(part_2(&input), tree::part_2(&input), "input:\n{}", input);
+        }
+    }
+
+    // Random reduced snailfish numbers: pairs nested at most four deep, regular numbers below 10
+    fn random_number(rng: &mut Rng, depth: usize) -> String {
+        if depth > 0 && (depth == 4 || rng.below(3) == 0) {
+            rng.below(10).to_string()
+        } else {
+            format!("[{},{}]", random_number(rng, depth + 1), random_number(rng, depth + 1))
+        }
+    }
+
+    fn random_input(rng: &mut Rng, n: usize) -> String {
+        (0..n).map(|_| random_number(rng, 0)).collect::<Vec<String>>().join("\n")
+    }
+
+    fn data() -> String {
+"[[[0,[5,8]],[[1,7],[9,6]]],[[4,[1,2]],[[1,4],2]]]
+[[[5,[2,8]],4],[5,[[9,9],0]]]
+[6,[[[6,2],[5,6]],[[7,6],[4,7]]]]
+[[[6,[0,7]],[0,9]],[4,[9,[9,0]]]]
+[[[7,[6,4]],[3,[1,3]]],[[[5,5],1],9]]
+[[6,[[7,3],[3,2]]],[[[3,8],[5,7]],4]]
+[[[[5,4],[7,7]],8],[[8,3],8]]
+[[9,3],[[9,9],[6,[4,9]]]]
+[[2,[[7,7],7]],[[5,8],[[9,3],[0,2]]]]
+[[[[5,2],5],[8,[3,7]]],[[5,[7,5]],[4,4]]]".to_string()
+    }
 }
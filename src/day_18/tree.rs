@@ -0,0 +1,394 @@
+use std::str::FromStr;
+
+use super::SnailfishNumber;
+
+// Which element of a pair a path step goes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+// A snailfish number with its pair structure, for inspecting and debugging what the flat
+// encoding does. Paths lead from the outermost pair down, the empty path is the whole number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnailfishTree {
+    Regular(u64),
+    Pair(Box<SnailfishTree>, Box<SnailfishTree>),
+}
+
+impl SnailfishTree {
+    pub fn pair(left: SnailfishTree, right: SnailfishTree) -> SnailfishTree {
+        SnailfishTree::Pair(Box::new(left), Box::new(right))
+    }
+
+    // `None` if the path goes through a regular number
+    #[allow(dead_code)]
+    pub fn get(&self, path: &[Side]) -> Option<&SnailfishTree> {
+        let mut node: &SnailfishTree = self;
+        for side in path {
+            node = match (node, side) {
+                (SnailfishTree::Pair(left, _), Side::Left) => left,
+                (SnailfishTree::Pair(_, right), Side::Right) => right,
+                (SnailfishTree::Regular(_), _) => return None,
+            };
+        }
+        Some(node)
+    }
+
+    pub fn get_mut(&mut self, path: &[Side]) -> Option<&mut SnailfishTree> {
+        let mut node: &mut SnailfishTree = self;
+        for side in path {
+            node = match (node, side) {
+                (SnailfishTree::Pair(left, _), Side::Left) => left,
+                (SnailfishTree::Pair(_, right), Side::Right) => right,
+                (SnailfishTree::Regular(_), _) => return None,
+            };
+        }
+        Some(node)
+    }
+
+    // Changes the regular number at `path` and returns the old one, `None` if there is no
+    // regular number at `path`
+    #[allow(dead_code)]
+    pub fn set(&mut self, path: &[Side], value: u64) -> Option<u64> {
+        match self.get_mut(path)? {
+            SnailfishTree::Regular(v) => Some(std::mem::replace(v, value)),
+            SnailfishTree::Pair(..) => None,
+        }
+    }
+
+    // Puts `subtree` at `path` and returns what was there, `None` if `path` doesn't exist
+    pub fn replace_subtree(&mut self, path: &[Side], subtree: SnailfishTree) -> Option<SnailfishTree> {
+        Some(std::mem::replace(self.get_mut(path)?, subtree))
+    }
+
+    // Every pair with its path, outer pairs before inner ones and left before right
+    pub fn pairs(&self) -> Pairs<'_> {
+        Pairs { stack: vec![(vec![], self)] }
+    }
+
+    pub fn add(self, other: SnailfishTree) -> SnailfishTree {
+        let mut result: SnailfishTree = SnailfishTree::pair(self, other);
+        result.reduce();
+        result
+    }
+
+    // Explodes the leftmost pair nested inside four pairs until there is none, then splits the
+    // leftmost regular number of 10 or greater, until there is nothing left to do
+    pub fn reduce(&mut self) {
+        loop {
+            if let Some(path) = self.pairs().find(|(path, _)| path.len() >= 4).map(|(path, _)| path) {
+                self.explode(&path);
+            } else if !self.split() {
+                break;
+            }
+        }
+    }
+
+    fn explode(&mut self, path: &[Side]) {
+        let Some(SnailfishTree::Pair(left, right)) = self.replace_subtree(path, SnailfishTree::Regular(0)) else {
+            panic!("no pair at {:?}", path);
+        };
+        let (SnailfishTree::Regular(l), SnailfishTree::Regular(r)) = (*left, *right) else {
+            panic!("pair at {:?} has a pair inside", path);
+        };
+
+        // The regular numbers just before and after the exploded pair, if any
+        if let Some(neighbour) = neighbour(path, Side::Left) {
+            *self.get_mut(&neighbour).unwrap().edge(Side::Right) += l;
+        }
+        if let Some(neighbour) = neighbour(path, Side::Right) {
+            *self.get_mut(&neighbour).unwrap().edge(Side::Left) += r;
+        }
+    }
+
+    // The leftmost or rightmost regular number
+    fn edge(&mut self, side: Side) -> &mut u64 {
+        let mut node: &mut SnailfishTree = self;
+        loop {
+            node = match (node, side) {
+                (SnailfishTree::Regular(v), _) => return v,
+                (SnailfishTree::Pair(left, _), Side::Left) => left,
+                (SnailfishTree::Pair(_, right), Side::Right) => right,
+            };
+        }
+    }
+
+    fn split(&mut self) -> bool {
+        let mut stack: Vec<&mut SnailfishTree> = vec![self];
+        while let Some(node) = stack.pop() {
+            match node {
+                SnailfishTree::Regular(v) if *v > 9 => {
+                    let (l, r): (u64, u64) = (*v / 2, v.div_ceil(2));
+                    *node = SnailfishTree::pair(SnailfishTree::Regular(l), SnailfishTree::Regular(r));
+                    return true;
+                }
+                SnailfishTree::Regular(_) => {}
+                SnailfishTree::Pair(left, right) => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+            }
+        }
+        false
+    }
+
+    // An error where it doesn't fit into 64 bits, like for the flat encoding
+    pub fn magnitude(&self) -> Result<u64, String> {
+        match self {
+            SnailfishTree::Regular(v) => Ok(*v),
+            SnailfishTree::Pair(left, right) => {
+                let (left, right): (u64, u64) = (left.magnitude()?, right.magnitude()?);
+                let magnitude: Option<u64> = left.checked_mul(3).and_then(|l| l.checked_add(right.checked_mul(2)?));
+                magnitude.ok_or_else(|| "magnitude overflows 64 bits".to_string())
+            }
+        }
+    }
+}
+
+// The path of the subtree next to `path` on `side` that shares the deepest common pair with it
+fn neighbour(path: &[Side], side: Side) -> Option<Vec<Side>> {
+    let other: Side = match side {
+        Side::Left => Side::Right,
+        Side::Right => Side::Left,
+    };
+    let turn: usize = path.iter().rposition(|&s| s == other)?;
+
+    let mut neighbour: Vec<Side> = path[..turn].to_vec();
+    neighbour.push(side);
+    Some(neighbour)
+}
+
+pub struct Pairs<'a> {
+    stack: Vec<(Vec<Side>, &'a SnailfishTree)>,
+}
+
+impl<'a> Iterator for Pairs<'a> {
+    type Item = (Vec<Side>, &'a SnailfishTree);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((path, node)) = self.stack.pop() {
+            if let SnailfishTree::Pair(left, right) = node {
+                let mut right_path: Vec<Side> = path.clone();
+                right_path.push(Side::Right);
+                let mut left_path: Vec<Side> = path.clone();
+                left_path.push(Side::Left);
+
+                self.stack.push((right_path, right));
+                self.stack.push((left_path, left));
+                return Some((path, node));
+            }
+        }
+        None
+    }
+}
+
+// Reads `[[1,2],[[3,4],5]]` without `parse_number`, so the tests comparing the two encodings
+// don't share a parser. Whitespace is skipped; it is meant for puzzle input and only checks
+// that the pairs are complete and the regular numbers fit into 16 bits, as for the flat encoding.
+impl FromStr for SnailfishTree {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // The finished elements of the open pairs, innermost last
+        let mut stack: Vec<SnailfishTree> = vec![];
+        let mut chars = s.chars().filter(|c| !c.is_whitespace()).peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '[' | ',' => {}
+                ']' => {
+                    let (Some(right), Some(left)) = (stack.pop(), stack.pop()) else {
+                        return Err(format!("{}: ']' without a pair to close", s));
+                    };
+                    stack.push(SnailfishTree::pair(left, right));
+                }
+                c => {
+                    let mut value: u64 = c.to_digit(10).ok_or_else(|| format!("{}: unexpected {:?}", s, c))? as u64;
+                    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                        value = value * 10 + digit as u64;
+                        if value > u16::MAX as u64 {
+                            return Err(format!("{}: regular number too large", s));
+                        }
+                        chars.next();
+                    }
+                    stack.push(SnailfishTree::Regular(value));
+                }
+            }
+        }
+
+        match (stack.pop(), stack.is_empty()) {
+            (Some(tree), true) => Ok(tree),
+            _ => Err(format!("{}: not a snailfish number", s)),
+        }
+    }
+}
+
+impl From<&SnailfishNumber> for SnailfishTree {
+    // Regular numbers in order with their depths: two neighbours at the same depth are a pair
+    fn from(n: &SnailfishNumber) -> Self {
+        let mut stack: Vec<(SnailfishTree, u16)> = vec![];
+
+        for (&value, &depth) in n.values.iter().zip(n.depths.iter()) {
+            stack.push((SnailfishTree::Regular(value), depth));
+
+            while stack.len() >= 2 && stack[stack.len() - 1].1 == stack[stack.len() - 2].1 {
+                let (right, depth): (SnailfishTree, u16) = stack.pop().unwrap();
+                let (left, _): (SnailfishTree, u16) = stack.pop().unwrap();
+                stack.push((SnailfishTree::pair(left, right), depth - 1));
+            }
+        }
+
+        assert!(stack.len() == 1 && stack[0].1 == 0, "not a snailfish number: {:?}", n);
+        stack.pop().unwrap().0
+    }
+}
+
+impl From<&SnailfishTree> for SnailfishNumber {
+    fn from(tree: &SnailfishTree) -> Self {
+        let mut n: SnailfishNumber = SnailfishNumber { values: vec![], depths: vec![] };
+
+        let mut stack: Vec<(&SnailfishTree, u16)> = vec![(tree, 0)];
+        while let Some((node, depth)) = stack.pop() {
+            match node {
+                SnailfishTree::Regular(value) => {
+                    n.values.push(*value);
+                    n.depths.push(depth);
+                }
+                SnailfishTree::Pair(left, right) => {
+                    stack.push((right, depth + 1));
+                    stack.push((left, depth + 1));
+                }
+            }
+        }
+        n
+    }
+}
+
+// One number per line, blank lines are skipped
+fn parse_trees(input: &str) -> Vec<SnailfishTree> {
+    input
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.parse().unwrap_or_else(|e| panic!("{}", e)))
+        .collect()
+}
+
+// The day's answers computed on trees, to benchmark against the flat encoding
+pub fn part_1(input: &str) -> u64 {
+    parse_trees(input).into_iter().reduce(SnailfishTree::add).unwrap().magnitude().unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn part_2(input: &str) -> u64 {
+    let numbers: Vec<SnailfishTree> = parse_trees(input);
+    let mut largest: u64 = 0;
+
+    for i in 0..numbers.len() {
+        for j in 0..numbers.len() {
+            if i != j {
+                let magnitude: u64 = numbers[i].clone().add(numbers[j].clone()).magnitude().unwrap_or_else(|e| panic!("{}", e));
+                largest = largest.max(magnitude);
+            }
+        }
+    }
+
+    largest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day_18::parse_number;
+    use Side::{Left, Right};
+
+    fn tree(input: &str) -> SnailfishTree {
+        input.parse().unwrap()
+    }
+
+    fn regular(value: u64) -> SnailfishTree {
+        SnailfishTree::Regular(value)
+    }
+
+    #[test]
+    fn conversion_works() {
+        let t = tree("[[1,2],[[3,4],5]]");
+
+        assert_eq!(
+            t,
+            SnailfishTree::pair(
+                SnailfishTree::pair(regular(1), regular(2)),
+                SnailfishTree::pair(SnailfishTree::pair(regular(3), regular(4)), regular(5))
+            )
+        );
+
+        let n = SnailfishNumber::from(&t);
+
+        assert_eq!(n.values, vec![1, 2, 3, 4, 5]);
+        assert_eq!(n.depths, vec![2, 2, 3, 3, 2]);
+        assert_eq!(SnailfishTree::from(&n), t);
+        assert_eq!(SnailfishTree::from(&parse_number(" [[1, 2],\t[[3,4] , 5]]").unwrap()), t);
+        assert_eq!(tree("12"), regular(12));
+    }
+
+    #[test]
+    fn malformed_trees_are_rejected() {
+        assert!("[1,2".parse::<SnailfishTree>().is_err());
+        assert!("[1,2]]".parse::<SnailfishTree>().is_err());
+        assert!("[1,x]".parse::<SnailfishTree>().is_err());
+        assert!("[1,65536]".parse::<SnailfishTree>().is_err());
+        assert!("".parse::<SnailfishTree>().is_err());
+    }
+
+    #[test]
+    fn paths_work() {
+        let mut t = tree("[[1,2],[[3,4],5]]");
+
+        assert_eq!(t.get(&[Right, Left, Right]), Some(&regular(4)));
+        assert_eq!(t.get(&[Left, Left, Left]), None);
+        assert_eq!(t.get(&[]), Some(&t.clone()));
+
+        assert_eq!(t.set(&[Left, Right], 7), Some(2));
+        assert_eq!(t.set(&[Right, Left], 7), None);
+        assert_eq!(t.replace_subtree(&[Right, Left], regular(9)), Some(tree("[3,4]")));
+        assert_eq!(t.replace_subtree(&[Left, Left, Right], regular(9)), None);
+        assert_eq!(t, tree("[[1,7],[9,5]]"));
+    }
+
+    #[test]
+    fn pairs_works() {
+        let t = tree("[[1,2],[[3,4],5]]");
+
+        let pairs: Vec<(Vec<Side>, u64)> = t.pairs().map(|(path, pair)| (path, pair.magnitude().unwrap())).collect();
+
+        assert_eq!(pairs, vec![(vec![], 3 * 7 + 2 * 61), (vec![Left], 7), (vec![Right], 61), (vec![Right, Left], 17)]);
+    }
+
+    #[test]
+    fn reduce_works() {
+        assert_eq!(tree("[[[[4,3],4],4],[7,[[8,4],9]]]").add(tree("[1,1]")), tree("[[[[0,7],4],[[7,8],[6,0]]],[8,1]]"));
+
+        let mut t = tree("[[[[[9,8],1],2],3],4]");
+        t.reduce();
+
+        assert_eq!(t, tree("[[[[0,9],2],3],4]"));
+
+        let mut t = tree("[7,[6,[5,[4,[3,2]]]]]");
+        t.reduce();
+
+        assert_eq!(t, tree("[7,[6,[5,[7,0]]]]"));
+    }
+
+    #[test]
+    fn parts_match_the_flat_encoding() {
+        let input = "\
+[[[0,[5,8]],[[1,7],[9,6]]],[[4,[1,2]],[[1,4],2]]]
+[[[5,[2,8]],4],[5,[[9,9],0]]]
+[6,[[[6,2],[5,6]],[[7,6],[4,7]]]]
+[[[6,[0,7]],[0,9]],[4,[9,[9,0]]]]
+[[[7,[6,4]],[3,[1,3]]],[[[5,5],1],9]]";
+
+        assert_eq!(part_1(input), super::super::part_1(input));
+        assert_eq!(part_2(input), super::super::part_2(input));
+    }
+}
@@ -1,5 +1,7 @@
 use std::collections::HashSet;
 
+use crate::visualize::{Color, FrameBuffer, Visualizer};
+
 const OCCURENCE_MAP_LEN: usize = 20_000;
 const OCCURENCE_MAP_OFFSET: isize = 10_000;
 
@@ -26,11 +28,42 @@ pub fn part_2(input: &str) -> usize {
     }).max().unwrap()
 }
 
+// Beacons and scanners relative to scanner 0, projected onto each of the three axis planes
+pub fn visualize(input: &str, visualizer: &mut Visualizer) {
+    const W: usize = 100;
+    const H: usize = 40;
+
+    let mut measurements: Vec<ScannerMeasurement> = parse_input(input);
+    let (beacons, scanners) = align_scans(&mut measurements);
+
+    for (name, u, v) in [("x-y", 0, 1), ("x-z", 0, 2), ("y-z", 1, 2)] {
+        let points = beacons.iter().chain(scanners.iter());
+        let (u_min, u_max) = points.clone().fold((i16::MAX, i16::MIN), |(lo, hi), p| (lo.min(p[u]), hi.max(p[u])));
+        let (v_min, v_max) = points.fold((i16::MAX, i16::MIN), |(lo, hi), p| (lo.min(p[v]), hi.max(p[v])));
+
+        let project = |p: &[i16; 3]| -> (usize, usize) {
+            let col: usize = (p[u] - u_min) as usize * (W - 1) / (u_max - u_min).max(1) as usize;
+            let row: usize = (v_max - p[v]) as usize * (H - 1) / (v_max - v_min).max(1) as usize;
+            (col, row)
+        };
+
+        let mut frame: FrameBuffer = FrameBuffer::new(W, H);
+        for beacon in &beacons {
+            let (col, row) = project(beacon);
+            frame.set(col, row, '.', Some(Color::Cyan));
+        }
+        for scanner in &scanners {
+            let (col, row) = project(scanner);
+            frame.set(col, row, 'S', Some(Color::Red));
+        }
+        visualizer.show(&frame, &format!("{} plane: {} beacons, {} scanners", name, beacons.len(), scanners.len()));
+    }
+}
 
 fn parse_input(input: &str) -> Vec<ScannerMeasurement> {
     let mut result: Vec<ScannerMeasurement> = vec![];
 
-    for line in input.lines().into_iter() {
+    for line in input.lines() {
         // Save the previous scanner reading and proceed
         if line.is_empty() {
             continue;
@@ -166,7 +199,7 @@ fn align_scans(measurements: &mut [ScannerMeasurement]) -> (HashSet<[i16; 3]>, H
                                         let y: i16 = measurements[dst_id].data[dst_perm[1]][j];
                                         let z: i16 = measurements[dst_id].data[dst_perm[2]][j];
 
-                                        beacons_relative_to_0.insert([x, y, z].into());
+                                        beacons_relative_to_0.insert([x, y, z]);
                                     }
 
                                     scanners_relative_to_0.insert(offsets);
@@ -1,40 +1,56 @@
 
+use crate::visualize::{Color, FrameBuffer, Visualizer};
+
 type ImageEnhancementAlgorithm = Vec<bool>;
 type Image = Vec<Vec<bool>>;
 
 pub fn part_1(input: &str) -> usize {
 
     let (mut img, iea): (Image, ImageEnhancementAlgorithm) = parse_input(input);
-    
-    // Test
-    // img = enhance(&img, &iea, false);
-    // img = enhance(&img, &iea, false);
+    let mut background: bool = false;
 
-    // Input
-    img = enhance(&img, &iea, false);
-    img = enhance(&img, &iea, true);
+    for _ in 0..2 {
+        img = enhance(&img, &iea, background);
+        background = next_background(background, &iea);
+    }
 
-    img.iter()
-       .fold(0, |acc, e| acc + 
-            e
-            .iter()
-            .fold(0, |acc_inner, e_inner| acc_inner + *e_inner as usize)
-        )
+    count_lit(&img)
 }
 
 pub fn part_2(input: &str) -> usize {
     let (mut img, iea): (Image, ImageEnhancementAlgorithm) = parse_input(input);
-    
-    // Test
-    // for _ in 0..50 {
-    //     img = enhance(&img, &iea, false);
-    // }
+    let mut background: bool = false;
 
-    // Input
-    for i in 0..50 {
-        img = enhance(&img, &iea, (i % 2) == 1);
+    for _ in 0..50 {
+        img = enhance(&img, &iea, background);
+        background = next_background(background, &iea);
     }
 
+    count_lit(&img)
+}
+
+pub fn visualize(input: &str, visualizer: &mut Visualizer) {
+    let (mut img, iea): (Image, ImageEnhancementAlgorithm) = parse_input(input);
+    let mut background: bool = false;
+
+    for step in 0..=50 {
+        let mut frame: FrameBuffer = FrameBuffer::new(img[0].len(), img.len());
+        for (row, pixels) in img.iter().enumerate() {
+            for (col, &pixel) in pixels.iter().enumerate() {
+                match pixel {
+                    true => frame.set(col, row, '#', Some(Color::Yellow)),
+                    false => frame.set(col, row, '.', Some(Color::Gray)),
+                }
+            }
+        }
+        visualizer.show(&frame, &format!("step {:>2}: {} lit", step, count_lit(&img)));
+
+        img = enhance(&img, &iea, background);
+        background = next_background(background, &iea);
+    }
+}
+
+fn count_lit(img: &Image) -> usize {
     img.iter()
        .fold(0, |acc, e| acc + 
             e
@@ -43,7 +59,6 @@ pub fn part_2(input: &str) -> usize {
         )
 }
 
-
 fn parse_input(input: &str) -> (Image, ImageEnhancementAlgorithm) {
 
     let mut img: Image = vec![];
@@ -51,7 +66,7 @@ fn parse_input(input: &str) -> (Image, ImageEnhancementAlgorithm) {
 
     let mut parsing_algorithm: bool = true;
     for line in input.lines() {
-        if line == "" {
+        if line.is_empty() {
             parsing_algorithm = false;
         }
         else if parsing_algorithm {
@@ -65,6 +80,15 @@ fn parse_input(input: &str) -> (Image, ImageEnhancementAlgorithm) {
     (img, iea)
 }
 
+// The infinite background is uniform, so its next value is given by the all-dark
+// (index 0) or all-lit (index 511) entry of the algorithm
+fn next_background(background: bool, iea: &ImageEnhancementAlgorithm) -> bool {
+    match background {
+        true => iea[511],
+        false => iea[0],
+    }
+}
+
 fn add_borders(img_in: &Image, pad_value: bool) -> Image {
 
     let w_in: usize = img_in[0].len();    
@@ -104,15 +128,15 @@ fn enhance(img: &Image, iea: &ImageEnhancementAlgorithm, pad_value: bool) -> Ima
         for col in 1..=(w+2) {
             let mut idx: usize = 0;
 
-            idx = idx | ((img_in[row-1] [col-1]    as usize) << 8);
-            idx = idx | ((img_in[row-1]   [col]    as usize) << 7);
-            idx = idx | ((img_in[row-1] [col+1]    as usize) << 6);
-            idx = idx | ((img_in  [row] [col-1]    as usize) << 5);
-            idx = idx | ((img_in  [row]   [col]    as usize) << 4);
-            idx = idx | ((img_in  [row] [col+1]    as usize) << 3);
-            idx = idx | ((img_in[row+1] [col-1]    as usize) << 2);
-            idx = idx | ((img_in[row+1]   [col]    as usize) << 1);
-            idx = idx | ((img_in[row+1] [col+1]    as usize));
+            idx |= (img_in[row-1] [col-1]    as usize) << 8;
+            idx |= (img_in[row-1]   [col]    as usize) << 7;
+            idx |= (img_in[row-1] [col+1]    as usize) << 6;
+            idx |= (img_in  [row] [col-1]    as usize) << 5;
+            idx |= (img_in  [row]   [col]    as usize) << 4;
+            idx |= (img_in  [row] [col+1]    as usize) << 3;
+            idx |= (img_in[row+1] [col-1]    as usize) << 2;
+            idx |= (img_in[row+1]   [col]    as usize) << 1;
+            idx |=  img_in[row+1] [col+1]    as usize;
 
             img_out[row-1][col-1] = iea[idx];
         }
@@ -123,6 +147,8 @@ fn enhance(img: &Image, iea: &ImageEnhancementAlgorithm, pad_value: bool) -> Ima
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::Rng;
+    use std::collections::HashSet;
 
     #[test]
     fn part_1_works() {
@@ -138,6 +164,99 @@ mod tests {
         assert_eq!(result, 3351);
     }
 
+    #[test]
+    fn part_1_matches_reference() {
+        let mut rng = Rng::new(20);
+
+        for _ in 0..1000 {
+            let input: String = random_input(&mut rng);
+
+            assert_eq!(part_1(&input), reference_count(&input, 2), "input:\n{}", input);
+        }
+    }
+
+    #[test]
+    fn part_2_matches_reference() {
+        let mut rng = Rng::new(2020);
+
+        for _ in 0..40 {
+            let input: String = random_input(&mut rng);
+
+            assert_eq!(part_2(&input), reference_count(&input, 50), "input:\n{}", input);
+        }
+    }
+
+    // Reference implementation: the set of lit pixels plus the value of the infinite background
+    fn reference_count(input: &str, steps: usize) -> usize {
+        let (img, iea): (Image, ImageEnhancementAlgorithm) = parse_input(input);
+
+        let mut lit: HashSet<(isize, isize)> = HashSet::new();
+        for (row, pixels) in img.iter().enumerate() {
+            for (col, &pixel) in pixels.iter().enumerate() {
+                if pixel {
+                    lit.insert((row as isize, col as isize));
+                }
+            }
+        }
+
+        let (mut min, mut max): (isize, isize) = (0, img.len().max(img[0].len()) as isize - 1);
+        let mut background: bool = false;
+
+        for _ in 0..steps {
+            let is_lit = |row: isize, col: isize| -> bool {
+                if row < min || row > max || col < min || col > max {
+                    background
+                } else {
+                    lit.contains(&(row, col))
+                }
+            };
+
+            let mut lit_next: HashSet<(isize, isize)> = HashSet::new();
+            for row in (min - 1)..=(max + 1) {
+                for col in (min - 1)..=(max + 1) {
+                    let mut idx: usize = 0;
+                    for d_row in -1..=1 {
+                        for d_col in -1..=1 {
+                            idx = (idx << 1) | is_lit(row + d_row, col + d_col) as usize;
+                        }
+                    }
+                    if iea[idx] {
+                        lit_next.insert((row, col));
+                    }
+                }
+            }
+
+            lit = lit_next;
+            background = iea[if background { 511 } else { 0 }];
+            min -= 1;
+            max += 1;
+        }
+
+        assert!(!background, "infinitely many pixels are lit");
+        lit.len()
+    }
+
+    fn random_input(rng: &mut Rng) -> String {
+        let mut iea: Vec<bool> = (0..512).map(|_| rng.bool()).collect();
+
+        // Keep the number of lit pixels finite after an even number of steps
+        if iea[0] {
+            iea[511] = false;
+        }
+
+        let mut input: String = iea.iter().map(|&b| if b { '#' } else { '.' }).collect();
+        input.push_str("\n\n");
+
+        let h: usize = 1 + rng.below(8);
+        let w: usize = 1 + rng.below(8);
+        for _ in 0..h {
+            input.extend((0..w).map(|_| if rng.bool() { '#' } else { '.' }));
+            input.push('\n');
+        }
+
+        input
+    }
+
     fn data() -> String {
 "..#.#..#####.#.#.#.###.##.....###.##.#..###.####..#####..#....#..#..##..##
 #..######.###...####..#..#####..##..#.#####...##.#.#..#.##..#.#......#.###
@@ -9,7 +9,7 @@ const PART_2_DICE_RESULTS: [usize; 27] = [3, 4, 5, 4, 5, 6, 5, 6, 7, 4, 5, 6, 5,
 
 pub fn part_1(input: &str) -> usize {
 
-    const SCORE_SEQUENCE: [[[usize; PART_1_POINTS]; NUM_FIELDS]; NUM_PLAYERS] = precompute_part_1();
+    static SCORE_SEQUENCE: [[[usize; PART_1_POINTS]; NUM_FIELDS]; NUM_PLAYERS] = precompute_part_1();
 
     let (player_1_start, player_2_start): (usize, usize) = parse_input(input);
 
@@ -66,8 +66,8 @@ pub fn part_2(input: &str) -> usize {
 fn parse_input(input: &str) -> (usize, usize) {
     let mut lines: std::str::Lines<'_> = input.lines();
 
-    let player_1_start: usize = lines.next().unwrap().split(' ').last().unwrap().parse::<usize>().unwrap() - 1;
-    let player_2_start: usize = lines.next().unwrap().split(' ').last().unwrap().parse::<usize>().unwrap() - 1;
+    let player_1_start: usize = lines.next().unwrap().split(' ').next_back().unwrap().parse::<usize>().unwrap() - 1;
+    let player_2_start: usize = lines.next().unwrap().split(' ').next_back().unwrap().parse::<usize>().unwrap() - 1;
 
     (player_1_start, player_2_start)
 }
@@ -205,6 +205,7 @@ const fn _precompute_part_2<const START: usize>() -> [usize; PART_2_POINTS] {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
 
     #[test]
     fn part_1_works() {
@@ -220,6 +221,92 @@ mod tests {
         assert_eq!(result, 444356092776315);
     }
 
+    // The input space is only 10x10 starting positions, so compare all of them
+    #[test]
+    fn part_1_matches_reference() {
+        for p1 in 1..=10 {
+            for p2 in 1..=10 {
+                let result = part_1(&input(p1, p2));
+
+                assert_eq!(result, reference_part_1(p1, p2), "starting positions {} and {}", p1, p2);
+            }
+        }
+    }
+
+    #[test]
+    fn part_2_matches_reference() {
+        let mut memo: HashMap<[usize; 5], [usize; 2]> = HashMap::new();
+
+        for p1 in 1..=10 {
+            for p2 in 1..=10 {
+                let result = part_2(&input(p1, p2));
+                let wins: [usize; 2] = reference_wins([p1, p2], [0, 0], 0, &mut memo);
+
+                assert_eq!(result, max(wins[0], wins[1]), "starting positions {} and {}", p1, p2);
+            }
+        }
+    }
+
+    // Reference implementation: play the game with the deterministic die
+    fn reference_part_1(p1: usize, p2: usize) -> usize {
+        let mut positions: [usize; 2] = [p1, p2];
+        let mut scores: [usize; 2] = [0, 0];
+        let mut die: usize = 0;
+        let mut player: usize = 0;
+
+        loop {
+            let mut steps: usize = 0;
+            for _ in 0..3 {
+                steps += die % 100 + 1;
+                die += 1;
+            }
+
+            positions[player] = (positions[player] + steps - 1) % 10 + 1;
+            scores[player] += positions[player];
+
+            if scores[player] >= 1000 {
+                return die * scores[1 - player];
+            }
+            player = 1 - player;
+        }
+    }
+
+    // Reference implementation: recursively split the universe for every roll of the Dirac die
+    fn reference_wins(positions: [usize; 2], scores: [usize; 2], player: usize, memo: &mut HashMap<[usize; 5], [usize; 2]>) -> [usize; 2] {
+        let key: [usize; 5] = [positions[0], positions[1], scores[0], scores[1], player];
+        if let Some(&wins) = memo.get(&key) {
+            return wins;
+        }
+
+        let mut wins: [usize; 2] = [0, 0];
+        for a in 1..=3 {
+            for b in 1..=3 {
+                for c in 1..=3 {
+                    let mut next_positions: [usize; 2] = positions;
+                    let mut next_scores: [usize; 2] = scores;
+
+                    next_positions[player] = (positions[player] + a + b + c - 1) % 10 + 1;
+                    next_scores[player] += next_positions[player];
+
+                    if next_scores[player] >= 21 {
+                        wins[player] += 1;
+                    } else {
+                        let sub_wins: [usize; 2] = reference_wins(next_positions, next_scores, 1 - player, memo);
+                        wins[0] += sub_wins[0];
+                        wins[1] += sub_wins[1];
+                    }
+                }
+            }
+        }
+
+        memo.insert(key, wins);
+        wins
+    }
+
+    fn input(p1: usize, p2: usize) -> String {
+        format!("Player 1 starting position: {}\nPlayer 2 starting position: {}", p1, p2)
+    }
+
     fn data() -> String {
 "Player 1 starting position: 4
 Player 2 starting position: 8".to_string()
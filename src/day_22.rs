@@ -1,6 +1,8 @@
 use regex::Regex;
 use std::cmp::{min, max};
 
+use crate::visualize::{Color, FrameBuffer, Visualizer};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Point3D {
     x: isize,
@@ -150,26 +152,13 @@ pub fn part_1(input: &str) -> isize {
 
     let command_boxes: Vec<Box3D> = input
         .lines()
-        .filter_map(|line| parse_box(&line, &re, Some(&filter)))
+        .filter_map(|line| parse_box(line, &re, Some(&filter)))
         .collect();
 
-    let mut boxes: Vec<Box3D> = vec![command_boxes.first().unwrap().clone()];
-
-    for command_box in &command_boxes[1..command_boxes.len()] {
-
-        let mut boxes_next: Vec<Box3D> = boxes.iter()
-                                                .flat_map(|b| b.subtract(command_box))
-                                                .collect();
-
-        if command_box.on {
-            boxes_next.push(command_box.clone());
-        }        
-        boxes = boxes_next;
-    }
-
-    boxes.iter()
+    reboot(&command_boxes)
+            .iter()
             .filter(|b| b.on)
-            .fold(0, |acc, b| acc + b.volume()) as isize
+            .fold(0, |acc, b| acc + b.volume())
 }
 
 pub fn part_2(input: &str) -> isize {
@@ -177,9 +166,58 @@ pub fn part_2(input: &str) -> isize {
  
     let command_boxes: Vec<Box3D> = input
         .lines()
-        .filter_map(|line| parse_box(&line, &re, None))
+        .filter_map(|line| parse_box(line, &re, None))
         .collect();
 
+    reboot(&command_boxes)
+         .iter()
+         .filter(|b| b.on)
+         .fold(0, |acc, b| acc + b.volume())
+}
+
+// Cross-sections of the part 1 region along the z axis, two rows of cubes per line
+pub fn visualize(input: &str, visualizer: &mut Visualizer) {
+    let re: Regex = Regex::new(r"(on|off) x=(-?\d+)\.\.(-?\d+),y=(-?\d+)\.\.(-?\d+),z=(-?\d+)\.\.(-?\d+)").unwrap();
+    let filter: Box3D = Box3D::new(Point3D::new(-50, -50, -50), Point3D::new(50, 50, 50), false);
+
+    let command_boxes: Vec<Box3D> = input
+        .lines()
+        .filter_map(|line| parse_box(line, &re, Some(&filter)))
+        .collect();
+    let boxes: Vec<Box3D> = reboot(&command_boxes);
+
+    for z in -50..=50 {
+        let mut slice: [[bool; 101]; 101] = [[false; 101]; 101];
+        let mut count: usize = 0;
+
+        for b in boxes.iter().filter(|b| b.on && b.min_corner.z <= z && z <= b.max_corner.z) {
+            for y in b.min_corner.y..=b.max_corner.y {
+                for x in b.min_corner.x..=b.max_corner.x {
+                    slice[(y + 50) as usize][(x + 50) as usize] = true;
+                    count += 1;
+                }
+            }
+        }
+
+        let mut frame: FrameBuffer = FrameBuffer::new(101, 51);
+        for (row, rows) in slice.chunks(2).enumerate() {
+            for (col, &upper) in rows[0].iter().enumerate() {
+                let lower: bool = rows.get(1).is_some_and(|r| r[col]);
+                let ch: char = match (upper, lower) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                };
+                frame.set(col, row, ch, Some(Color::Green));
+            }
+        }
+        visualizer.show(&frame, &format!("z = {:>3}: {} cubes on", z, count));
+    }
+}
+
+// Applies the commands in order, keeping a list of disjoint boxes
+fn reboot(command_boxes: &[Box3D]) -> Vec<Box3D> {
     let mut boxes: Vec<Box3D> = vec![command_boxes.first().unwrap().clone()];
 
     for command_box in &command_boxes[1..command_boxes.len()] {
@@ -194,14 +232,14 @@ pub fn part_2(input: &str) -> isize {
         boxes = boxes_next;
     }
 
-    boxes.iter()
-         .filter(|b| b.on)
-         .fold(0, |acc, b| acc + b.volume()) as isize
+    boxes
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::Rng;
+    use std::collections::HashSet;
 
     #[test]
     fn part_1a_works() {
@@ -219,11 +257,83 @@ mod tests {
 
     #[test]
     fn part_2_works() {
-        let result = part_2(&&p2_data());
+        let result = part_2(&p2_data());
 
         assert_eq!(result, 2758514936282235);
     }
 
+    #[test]
+    fn part_1_matches_reference() {
+        let mut rng = Rng::new(22);
+
+        for _ in 0..1000 {
+            let input: String = random_input(&mut rng);
+
+            assert_eq!(part_1(&input), reference_count(&input, Some(50)), "input:\n{}", input);
+        }
+    }
+
+    #[test]
+    fn part_2_matches_reference() {
+        let mut rng = Rng::new(2222);
+
+        for _ in 0..1000 {
+            let input: String = random_input(&mut rng);
+
+            assert_eq!(part_2(&input), reference_count(&input, None), "input:\n{}", input);
+        }
+    }
+
+    // Reference implementation: switch individual cubes in a set
+    fn reference_count(input: &str, limit: Option<isize>) -> isize {
+        let re = Regex::new(r"(on|off) x=(-?\d+)\.\.(-?\d+),y=(-?\d+)\.\.(-?\d+),z=(-?\d+)\.\.(-?\d+)").unwrap();
+        let mut cubes: HashSet<(isize, isize, isize)> = HashSet::new();
+
+        for line in input.lines() {
+            let b: Box3D = parse_box(line, &re, None).unwrap();
+
+            for x in b.min_corner.x..=b.max_corner.x {
+                for y in b.min_corner.y..=b.max_corner.y {
+                    for z in b.min_corner.z..=b.max_corner.z {
+                        if let Some(l) = limit {
+                            if x.abs() > l || y.abs() > l || z.abs() > l {
+                                continue;
+                            }
+                        }
+
+                        if b.on {
+                            cubes.insert((x, y, z));
+                        } else {
+                            cubes.remove(&(x, y, z));
+                        }
+                    }
+                }
+            }
+        }
+
+        cubes.len() as isize
+    }
+
+    fn random_input(rng: &mut Rng) -> String {
+        let mut lines: Vec<String> = vec![];
+
+        for i in 0..(1 + rng.below(10)) {
+            // The first cuboid always lies within the part 1 region
+            let (lo, hi): (isize, isize) = if i == 0 { (-45, 40) } else { (-60, 55) };
+
+            let mut ranges: Vec<String> = vec![];
+            for axis in ["x", "y", "z"] {
+                let start: isize = rng.range(lo, hi);
+                ranges.push(format!("{}={}..{}", axis, start, start + rng.range(0, 5)));
+            }
+
+            let command: &str = if i == 0 || rng.bool() { "on" } else { "off" };
+            lines.push(format!("{} {}", command, ranges.join(",")));
+        }
+
+        lines.join("\n")
+    }
+
     fn p1a_data() -> String {
 "on x=10..12,y=10..12,z=10..12
 on x=11..13,y=11..13,z=11..13
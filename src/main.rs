@@ -1,21 +1,123 @@
 mod benchmark;
+mod cli;
+mod config;
+mod serve;
+mod visualize;
+mod watch;
+#[cfg(test)]
+mod testing;
 
-// mod day_16;
-// mod day_18;
-// mod day_19;
-// mod day_20;
-// mod day_21;
+mod day_16;
+mod day_18;
+mod day_19;
+mod day_20;
+mod day_21;
 mod day_22;
 
-use crate::benchmark::{benchmark_run, print_day, print_header};
+use crate::benchmark::{benchmark_all, read_input, read_input_bytes, Solver};
+use crate::cli::{parse_args, Args, Command, USAGE};
+use crate::config::Config;
+use crate::day_16::{DecodeOptions, Encoding};
+use crate::visualize::Visualizer;
 
 fn main() {
-    benchmark_all!(
-        // day_16,
-        // day_18,
-        // day_19,
-        // day_20,
-        // day_21,
+    let solvers: Vec<Solver> = solvers!(
+        day_16,
+        day_18,
+        day_18::tree,
+        day_19,
+        day_20,
+        day_21,
         day_22
     );
-}
\ No newline at end of file
+
+    let config: Config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let raw_args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Args = match parse_args(&raw_args, config) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    match args.command {
+        Command::Benchmark => benchmark_all(&solvers, &args.config),
+        Command::Watch => watch::watch(args.config.days[0], &args.config),
+        Command::Visualize => visualize_day(&args),
+        Command::Emit => emit_day(&args),
+        Command::Compile => match day_16::compile(&args.expression) {
+            Ok(hex) => println!("{}", hex),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        Command::Serve => {
+            if let Err(e) = serve::serve(solvers, args.port, &args.config) {
+                eprintln!("Unable to serve on port {}: {}", args.port, e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+fn emit_day(args: &Args) {
+    let day: u8 = args.config.days[0];
+    let emit: fn(&[u8], &str, &DecodeOptions) -> Result<String, String> = match day {
+        16 => day_16::emit,
+        _ => {
+            eprintln!("Day {:02} has no --emit formats", day);
+            std::process::exit(2);
+        }
+    };
+
+    let result: Result<String, String> = read_input_bytes(&args.config.inputs, day).and_then(|input: Vec<u8>| {
+        let encoding: Encoding = match args.config.encoding {
+            Encoding::Auto => {
+                let detected: Encoding = Encoding::detect(&input).map_err(|e| e.to_string())?;
+                eprintln!("Detected {} encoding", detected);
+                detected
+            }
+            encoding => encoding,
+        };
+        let options: DecodeOptions = DecodeOptions { strict: args.strict, encoding, ..DecodeOptions::default() };
+        emit(&input, &args.emit, &options)
+    });
+    match result {
+        Ok(output) => println!("{}", output.trim_end()),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn visualize_day(args: &Args) {
+    let day: u8 = args.config.days[0];
+    let visualize: fn(&str, &mut Visualizer) = match day {
+        18 => day_18::visualize,
+        19 => day_19::visualize,
+        20 => day_20::visualize,
+        22 => day_22::visualize,
+        _ => {
+            eprintln!("Day {:02} has no visualization", day);
+            std::process::exit(2);
+        }
+    };
+
+    match read_input(&args.config.inputs, day) {
+        Ok(input) => visualize(&input, &mut Visualizer::new(args.color, args.fps)),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
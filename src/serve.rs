@@ -0,0 +1,317 @@
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::benchmark::{measure_run, run_with_timeout, Part, Solver};
+use crate::config::Config;
+
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_HEAD_LEN: usize = 16 * 1024;
+const MAX_HEADERS: usize = 64;
+const MAX_BODY_LEN: usize = 16 * 1024 * 1024;
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    body: String,
+}
+
+impl Response {
+    fn json(body: String) -> Self {
+        Response { status: 200, body }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Response { status, body: format!("{{\"error\":{}}}", json_string(message)) }
+    }
+}
+
+// Serves the solvers over HTTP/1.1, one thread per connection:
+//   GET  /days                 -> registered days
+//   POST /solve/{day}/{part}   -> answer and timing for the puzzle input in the body
+// Inputs are read in the configured encoding, and solvers running longer than the configured
+// timeout are given up on like in the benchmark.
+pub fn serve(solvers: Vec<Solver>, port: u16, config: &Config) -> std::io::Result<()> {
+    let listener: TcpListener = TcpListener::bind(("127.0.0.1", port))?;
+    println!("Listening on http://{}", listener.local_addr()?);
+    serve_on(listener, Arc::new(solvers), Arc::new(config.clone()));
+    Ok(())
+}
+
+fn serve_on(listener: TcpListener, solvers: Arc<Vec<Solver>>, config: Arc<Config>) {
+    for stream in listener.incoming().flatten() {
+        let solvers: Arc<Vec<Solver>> = Arc::clone(&solvers);
+        let config: Arc<Config> = Arc::clone(&config);
+        std::thread::spawn(move || handle_connection(stream, &solvers, &config));
+    }
+}
+
+fn handle_connection(stream: TcpStream, solvers: &[Solver], config: &Config) {
+    // Clients that stop sending don't hold on to their thread forever
+    if stream.set_read_timeout(Some(READ_TIMEOUT)).is_err() {
+        return;
+    }
+    let mut reader: BufReader<&TcpStream> = BufReader::new(&stream);
+
+    let response: Response = match read_request(&mut reader) {
+        Ok(request) => route(&request, solvers, config),
+        Err(response) => response,
+    };
+
+    let reason: &str = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Content Too Large",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _ => "Unprocessable Content",
+    };
+    let head: String = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason,
+        response.body.len()
+    );
+
+    let mut writer: &TcpStream = &stream;
+    _ = writer.write_all(head.as_bytes());
+    _ = writer.write_all(response.body.as_bytes());
+    _ = writer.flush();
+}
+
+fn read_request(reader: &mut impl BufRead) -> Result<Request, Response> {
+    let mut head_len: usize = 0;
+    let request_line: String = read_head_line(reader, &mut head_len)?;
+
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(path), Some(_version)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(Response::error(400, "Malformed request line"));
+    };
+
+    let mut content_length: usize = 0;
+    let mut headers: usize = 0;
+    loop {
+        let line: String = read_head_line(reader, &mut head_len)?;
+        if line.is_empty() {
+            return Err(Response::error(400, "Unexpected end of headers"));
+        }
+
+        let line: &str = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        headers += 1;
+        if headers > MAX_HEADERS {
+            return Err(Response::error(431, &format!("More than {} headers", MAX_HEADERS)));
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().map_err(|_| Response::error(400, "Invalid Content-Length"))?;
+            }
+        }
+    }
+
+    if content_length > MAX_BODY_LEN {
+        return Err(Response::error(413, &format!("Body larger than {} bytes", MAX_BODY_LEN)));
+    }
+
+    let mut body: Vec<u8> = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(read_error)?;
+
+    Ok(Request { method: method.to_string(), path: path.to_string(), body })
+}
+
+// Reads one line of the request line and headers, which together may not exceed `MAX_HEAD_LEN`
+// bytes. Returns an empty string at the end of the stream.
+fn read_head_line(reader: &mut impl BufRead, head_len: &mut usize) -> Result<String, Response> {
+    let mut line: String = String::new();
+    let limit: u64 = (MAX_HEAD_LEN - *head_len) as u64;
+    *head_len += reader.take(limit).read_line(&mut line).map_err(read_error)?;
+
+    if *head_len == MAX_HEAD_LEN && !line.ends_with('\n') {
+        return Err(Response::error(431, &format!("Headers larger than {} bytes", MAX_HEAD_LEN)));
+    }
+    Ok(line)
+}
+
+fn read_error(e: std::io::Error) -> Response {
+    match e.kind() {
+        ErrorKind::WouldBlock | ErrorKind::TimedOut => Response::error(408, "Timed out reading the request"),
+        _ => Response::error(400, &e.to_string()),
+    }
+}
+
+fn route(request: &Request, solvers: &[Solver], config: &Config) -> Response {
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["days"]) => {
+            let days: Vec<String> = solvers.iter().filter(|s| s.variant.is_none()).map(|s| s.day.to_string()).collect();
+            Response::json(format!("{{\"days\":[{}]}}", days.join(",")))
+        }
+        ("POST", ["solve", day, part]) => solve(solvers, day, part, &request.body, config),
+        (_, ["days"]) | (_, ["solve", _, _]) => Response::error(405, "Method not allowed"),
+        _ => Response::error(404, "Not found"),
+    }
+}
+
+fn solve(solvers: &[Solver], day: &str, part: &str, body: &[u8], config: &Config) -> Response {
+    let Some(solver) = day.parse::<u8>().ok().and_then(|d| solvers.iter().find(|s| s.day == d && s.variant.is_none())) else {
+        return Response::error(404, &format!("Unknown day {}", day));
+    };
+
+    let f: Part = match part {
+        "1" => solver.part_1,
+        "2" => solver.part_2,
+        _ => return Response::error(404, &format!("Unknown part {}", part)),
+    };
+
+    // The solvers assume well-formed puzzle input and panic otherwise
+    let run = match config.timeout {
+        Some(timeout) => run_with_timeout(f, body, config.encoding, timeout),
+        None => catch_unwind(AssertUnwindSafe(|| measure_run(&|input: &[u8]| f(input, config.encoding), body))).map_err(|_| "failed"),
+    };
+
+    match run {
+        Ok((Err(e), _)) => Response::error(400, &format!("Input is {}", e)),
+        Ok((Ok(answer), seconds)) => Response::json(format!(
+            "{{\"day\":{},\"part\":{},\"answer\":{},\"seconds\":{}}}",
+            solver.day,
+            part,
+            json_string(&answer),
+            seconds
+        )),
+        Err("timeout") => Response::error(503, "Solver timed out"),
+        Err(_) => Response::error(422, "Solver failed on the given input"),
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out: String = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day_16::Encoding;
+    use crate::{day_16, day_21};
+    use crate::solvers;
+    use std::io::Read;
+    use std::net::SocketAddr;
+
+    fn start_server(config: Config) -> SocketAddr {
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        let solvers: Vec<Solver> = solvers!(day_16, day_21);
+
+        std::thread::spawn(move || serve_on(listener, Arc::new(solvers), Arc::new(config)));
+        addr
+    }
+
+    fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, String) {
+        let mut stream: TcpStream = TcpStream::connect(addr).unwrap();
+        write!(stream, "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}", method, path, body.len(), body).unwrap();
+
+        let mut response: String = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let status: u16 = response[9..12].parse().unwrap();
+        let body: String = response.split_once("\r\n\r\n").unwrap().1.to_string();
+        (status, body)
+    }
+
+    #[test]
+    fn days_works() {
+        let addr = start_server(Config::default());
+
+        assert_eq!(request(addr, "GET", "/days", ""), (200, "{\"days\":[16,21]}".to_string()));
+    }
+
+    #[test]
+    fn solve_works() {
+        let addr = start_server(Config::default());
+        let input = "Player 1 starting position: 4\nPlayer 2 starting position: 8";
+
+        let (status, body) = request(addr, "POST", "/solve/21/2", input);
+
+        assert_eq!(status, 200);
+        assert!(body.starts_with("{\"day\":21,\"part\":2,\"answer\":\"444356092776315\",\"seconds\":"), "{}", body);
+    }
+
+    #[test]
+    fn errors_work() {
+        let addr = start_server(Config::default());
+
+        assert_eq!(request(addr, "POST", "/solve/17/1", "").0, 404);
+        assert_eq!(request(addr, "POST", "/solve/21/3", "").0, 404);
+        assert_eq!(request(addr, "GET", "/solve/21/1", "").0, 405);
+        assert_eq!(request(addr, "POST", "/solve/21/1", "garbage").0, 422);
+        assert_eq!(request(addr, "GET", "/", "").0, 404);
+    }
+
+    #[test]
+    fn encoding_works() {
+        let addr = start_server(Config { encoding: Encoding::Base64, ..Config::default() });
+
+        let (status, body) = request(addr, "POST", "/solve/16/1", "igBKgBqAAvR4");
+
+        assert_eq!(status, 200);
+        assert!(body.starts_with("{\"day\":16,\"part\":1,\"answer\":\"16\","), "{}", body);
+
+        let addr = start_server(Config { encoding: Encoding::Auto, ..Config::default() });
+
+        assert_eq!(
+            request(addr, "POST", "/solve/16/1", "C200B40A"),
+            (400, "{\"error\":\"Input is both hex and base64, pick an encoding\"}".to_string())
+        );
+    }
+
+    #[test]
+    fn timeout_works() {
+        let addr = start_server(Config { timeout: Some(0.000001), ..Config::default() });
+        let input = "Player 1 starting position: 4\nPlayer 2 starting position: 8";
+
+        assert_eq!(request(addr, "POST", "/solve/21/2", input), (503, "{\"error\":\"Solver timed out\"}".to_string()));
+    }
+
+    #[test]
+    fn head_limits_work() {
+        let status = |request: String| read_request(&mut request.as_bytes()).err().map(|response| response.status);
+
+        let headers: String = "X: y\r\n".repeat(MAX_HEADERS);
+        assert_eq!(status(format!("GET /days HTTP/1.1\r\n{}\r\n", headers)), None);
+        assert_eq!(status(format!("GET /days HTTP/1.1\r\n{}X: y\r\n\r\n", headers)), Some(431));
+        assert_eq!(status(format!("GET /days HTTP/1.1\r\nX: {}\r\n\r\n", "y".repeat(MAX_HEAD_LEN))), Some(431));
+        assert_eq!(status("GET /days HTTP/1.1\r\nX: y\r\n".to_string()), Some(400));
+    }
+
+    #[test]
+    fn json_string_works() {
+        assert_eq!(json_string("a\"b\\c\n\u{1}"), "\"a\\\"b\\\\c\\n\\u0001\"");
+    }
+}
@@ -0,0 +1,33 @@
+// Deterministic pseudo-random generator for the randomized tests (xorshift64*),
+// so that generated inputs are reproducible and no external crate is needed.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed.max(1) }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // Uniform value in `lo..=hi`
+    pub fn range(&mut self, lo: isize, hi: isize) -> isize {
+        let span: u64 = (hi - lo) as u64 + 1;
+        lo + (self.next_u64() % span) as isize
+    }
+
+    // Uniform value in `0..n`
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub fn bool(&mut self) -> bool {
+        self.next_u64() & 1 == 1
+    }
+}
@@ -0,0 +1,195 @@
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+const CLEAR_SCREEN: &str = "\x1b[2J";
+const CLEAR_LINE: &str = "\x1b[K";
+const CLEAR_BELOW: &str = "\x1b[J";
+const CURSOR_HOME: &str = "\x1b[H";
+const HIDE_CURSOR: &str = "\x1b[?25l";
+const SHOW_CURSOR: &str = "\x1b[?25h";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum Color {
+    Gray,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+impl Color {
+    fn code(&self) -> u8 {
+        match self {
+            Color::Gray => 90,
+            Color::Red => 31,
+            Color::Green => 32,
+            Color::Yellow => 33,
+            Color::Blue => 34,
+            Color::Magenta => 35,
+            Color::Cyan => 36,
+            Color::White => 97,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: char,
+    pub color: Option<Color>,
+}
+
+pub struct FrameBuffer {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        FrameBuffer { width, height, cells: vec![Cell { ch: ' ', color: None }; width * height] }
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Writes outside of the buffer are ignored, so callers don't need to clip
+    pub fn set(&mut self, x: usize, y: usize, ch: char, color: Option<Color>) {
+        if x < self.width && y < self.height {
+            self.cells[y * self.width + x] = Cell { ch, color };
+        }
+    }
+
+    pub fn text(&mut self, x: usize, y: usize, s: &str, color: Option<Color>) {
+        for (i, ch) in s.chars().enumerate() {
+            self.set(x + i, y, ch, color);
+        }
+    }
+
+    fn row(&self, y: usize) -> &[Cell] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+}
+
+// Draws frame buffers to the terminal at a fixed frame rate, each one over the previous one.
+// Without color, only the color codes are left out; the cursor is still moved and cleared.
+pub struct Visualizer {
+    color: bool,
+    frame_time: Duration,
+    last_frame: Option<Instant>,
+}
+
+impl Visualizer {
+    pub fn new(color: bool, fps: f64) -> Self {
+        Visualizer { color, frame_time: Duration::from_secs_f64(1. / fps), last_frame: None }
+    }
+
+    pub fn show(&mut self, frame: &FrameBuffer, caption: &str) {
+        let out: String = self.render(frame, caption);
+
+        if let Some(last_frame) = self.last_frame {
+            let elapsed: Duration = last_frame.elapsed();
+            if elapsed < self.frame_time {
+                std::thread::sleep(self.frame_time - elapsed);
+            }
+        }
+
+        let mut stdout = std::io::stdout().lock();
+        _ = stdout.write_all(out.as_bytes());
+        _ = stdout.flush();
+        self.last_frame = Some(Instant::now());
+    }
+
+    fn render(&self, frame: &FrameBuffer, caption: &str) -> String {
+        let mut out: String = String::new();
+
+        if self.last_frame.is_none() {
+            out.push_str(CLEAR_SCREEN);
+            out.push_str(HIDE_CURSOR);
+        }
+        out.push_str(CURSOR_HOME);
+
+        out.push_str(caption);
+        self.end_line(&mut out);
+
+        for y in 0..frame.height() {
+            let mut current: Option<Color> = None;
+
+            for cell in frame.row(y) {
+                if self.color && cell.color != current {
+                    match cell.color {
+                        Some(color) => out.push_str(&format!("\x1b[{}m", color.code())),
+                        None => out.push_str(RESET),
+                    }
+                    current = cell.color;
+                }
+                out.push(cell.ch);
+            }
+
+            if self.color && current.is_some() {
+                out.push_str(RESET);
+            }
+            self.end_line(&mut out);
+        }
+
+        out.push_str(CLEAR_BELOW);
+        out
+    }
+
+    fn end_line(&self, out: &mut String) {
+        out.push_str(CLEAR_LINE);
+        out.push('\n');
+    }
+}
+
+impl Drop for Visualizer {
+    fn drop(&mut self) {
+        if self.last_frame.is_some() {
+            print!("{}", SHOW_CURSOR);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame() -> FrameBuffer {
+        let mut frame = FrameBuffer::new(3, 2);
+        frame.text(0, 0, "ab", Some(Color::Red));
+        frame.set(2, 1, 'c', None);
+        frame.set(3, 1, 'x', None);
+        frame
+    }
+
+    #[test]
+    fn render_without_color_works() {
+        let visualizer = Visualizer::new(false, 10.);
+
+        assert_eq!(
+            visualizer.render(&frame(), "step 1"),
+            "\x1b[2J\x1b[?25l\x1b[H\
+             step 1\x1b[K\n\
+             ab \x1b[K\n  c\x1b[K\n\
+             \x1b[J"
+        );
+    }
+
+    #[test]
+    fn render_with_color_works() {
+        let visualizer = Visualizer::new(true, 10.);
+
+        assert_eq!(
+            visualizer.render(&frame(), "step 1"),
+            "\x1b[2J\x1b[?25l\x1b[H\
+             step 1\x1b[K\n\
+             \x1b[31mab\x1b[0m \x1b[K\n  c\x1b[K\n\
+             \x1b[J"
+        );
+    }
+}
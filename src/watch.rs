@@ -0,0 +1,247 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::time::{Duration, SystemTime};
+
+use crate::benchmark::{parse_tsv, PartResult};
+use crate::config::Config;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const W_LABEL: usize = 14;
+const W_ANSWER: usize = 40;
+
+// Modification times of the watched files, `None` for files that can't be read
+type Snapshot = Vec<(PathBuf, Option<SystemTime>)>;
+
+// Re-runs the examples and the benchmark of `day` whenever its source, input or example files change
+pub fn watch(day: u8, config: &Config) {
+    let mut last_snapshot: Snapshot = vec![];
+    let mut previous: Vec<PartResult> = vec![];
+
+    loop {
+        let snapshot: Snapshot = take_snapshot(day, &config.inputs);
+
+        if snapshot != last_snapshot {
+            let changed: Vec<String> = snapshot
+                .iter()
+                .filter(|entry| !last_snapshot.contains(entry))
+                .map(|(path, _)| path.display().to_string())
+                .collect();
+            println!("day {:02}: {} changed", day, changed.join(", "));
+
+            run_examples(day);
+            if let Some(results) = run_benchmark(day, config) {
+                print_diff(&previous, &results);
+                previous = results;
+            }
+            println!();
+
+            last_snapshot = snapshot;
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+// The day's source files, `src/day_NN.rs` and the files of its submodules in `src/day_NN/`,
+// and every file in the `inputs` directory starting with the day number, e.g. `16.in` and
+// `16_example.in`
+fn watched_files(day: u8, inputs: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = vec![PathBuf::from(format!("src/day_{:02}.rs", day))];
+    let prefix: String = format!("{:02}", day);
+
+    files.extend(files_in(Path::new(&format!("src/day_{:02}", day)), |_| true));
+    files.extend(files_in(inputs, |name| name.starts_with(&prefix)));
+
+    files
+}
+
+// The files in `dir` whose name `keep` accepts, sorted, and none if `dir` can't be read
+fn files_in<F: Fn(&str) -> bool>(dir: &Path, keep: F) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.file_name().is_some_and(|name| keep(&name.to_string_lossy())))
+        .collect();
+    files.sort();
+    files
+}
+
+fn take_snapshot(day: u8, inputs: &Path) -> Snapshot {
+    watched_files(day, inputs)
+        .into_iter()
+        .map(|path| {
+            let modified: Option<SystemTime> = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+            (path, modified)
+        })
+        .collect()
+}
+
+fn cargo(args: &[&str]) -> Option<Output> {
+    match Command::new("cargo").args(args).output() {
+        Ok(output) => Some(output),
+        Err(e) => {
+            println!("{:<w$}unable to run cargo: {}", "error", e, w = W_LABEL);
+            None
+        }
+    }
+}
+
+fn print_build_failure(output: &Output) {
+    println!("{:<w$}build failed", "error", w = W_LABEL);
+    let stderr: String = String::from_utf8_lossy(&output.stderr).to_string();
+    for line in stderr.lines().filter(|l| l.starts_with("error")).take(5) {
+        println!("{:<w$}{}", "", line, w = W_LABEL);
+    }
+}
+
+fn run_examples(day: u8) {
+    let filter: String = format!("day_{:02}::", day);
+    let Some(output) = cargo(&["test", "--release", "--quiet", "--", &filter]) else {
+        return;
+    };
+
+    let stdout: String = String::from_utf8_lossy(&output.stdout).to_string();
+    match parse_test_summary(&stdout) {
+        Some((passed, failed)) => {
+            println!("{:<w$}{} passed, {} failed", "examples", passed, failed, w = W_LABEL);
+            for name in parse_failed_tests(&stdout) {
+                println!("{:<w$}FAILED {}", "", name, w = W_LABEL);
+            }
+        }
+        None => print_build_failure(&output),
+    }
+}
+
+// Benchmarks `day` on the input in the watched inputs directory, read in the configured encoding
+fn run_benchmark(day: u8, config: &Config) -> Option<Vec<PartResult>> {
+    let day_arg: String = day.to_string();
+    let inputs_arg: String = config.inputs.display().to_string();
+    let encoding_arg: String = config.encoding.to_string();
+    let output: Output = cargo(&[
+        "run", "--release", "--quiet", "--", "--day", &day_arg, "--inputs", &inputs_arg, "--encoding", &encoding_arg, "--format", "tsv",
+    ])?;
+
+    if !output.status.success() {
+        print_build_failure(&output);
+        return None;
+    }
+
+    let results: Vec<PartResult> = parse_tsv(&String::from_utf8_lossy(&output.stdout));
+    if results.is_empty() {
+        println!("{:<w$}{}", "error", String::from_utf8_lossy(&output.stderr).trim(), w = W_LABEL);
+        return None;
+    }
+    Some(results)
+}
+
+// Sums up the "test result: ok. 2 passed; 0 failed; ..." lines of all test binaries
+fn parse_test_summary(stdout: &str) -> Option<(usize, usize)> {
+    let mut summary: Option<(usize, usize)> = None;
+
+    for line in stdout.lines().filter(|l| l.starts_with("test result:")) {
+        let count = |label: &str| -> usize {
+            line.split(';')
+                .find_map(|field| field.trim().strip_suffix(label))
+                .and_then(|n| n.rsplit(' ').find(|s| !s.is_empty()))
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(0)
+        };
+        let (passed, failed) = summary.unwrap_or((0, 0));
+        summary = Some((passed + count(" passed"), failed + count(" failed")));
+    }
+
+    summary
+}
+
+// Names listed in the last "failures:" block of the test output
+fn parse_failed_tests(stdout: &str) -> Vec<String> {
+    let Some(start) = stdout.rfind("\nfailures:\n") else {
+        return vec![];
+    };
+
+    stdout[start + 1..]
+        .lines()
+        .skip(1)
+        .take_while(|l| l.starts_with("    "))
+        .map(|l| l.trim().to_string())
+        .collect()
+}
+
+fn print_diff(previous: &[PartResult], results: &[PartResult]) {
+    for result in results {
+        let before: Option<&PartResult> = previous.iter().find(|p| p.variant == result.variant && p.part == result.part);
+
+        let answer: String = match before {
+            Some(b) if b.answer != result.answer => format!("{} (was {})", result.answer, b.answer),
+            _ => result.answer.clone(),
+        };
+
+        let mut duration: String = format!("{:.3} ms", result.seconds * 1000.);
+        if let Some(b) = before {
+            let change: f64 = (result.seconds / b.seconds - 1.) * 100.;
+            duration = format!("{} ({:+.1}%)", duration, change);
+        }
+
+        let label: String = match &result.variant {
+            Some(variant) => format!("{} part {}", variant, result.part),
+            None => format!("part {}", result.part),
+        };
+        println!("{:<w$}{:<a$}{}", label, answer, duration, w = W_LABEL, a = W_ANSWER);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test_summary_works() {
+        let stdout = "
+running 3 tests
+...
+test result: ok. 3 passed; 0 failed; 0 ignored; 0 measured; 20 filtered out; finished in 0.01s
+
+running 2 tests
+.F
+failures:
+
+---- day_16::tests::part_2_works stdout ----
+assertion failed
+
+failures:
+    day_16::tests::part_2_works
+
+test result: FAILED. 1 passed; 1 failed; 0 ignored; 0 measured; 20 filtered out; finished in 0.01s
+";
+
+        assert_eq!(parse_test_summary(stdout), Some((4, 1)));
+        assert_eq!(parse_failed_tests(stdout), vec!["day_16::tests::part_2_works".to_string()]);
+        assert_eq!(parse_test_summary("error[E0425]: cannot find value"), None);
+    }
+
+    #[test]
+    fn submodules_are_watched() {
+        let files = watched_files(16, Path::new("inputs"));
+
+        assert_eq!(files[0], PathBuf::from("src/day_16.rs"));
+        assert!(files.contains(&PathBuf::from("src/day_16/bits.rs")));
+        assert!(watched_files(18, Path::new("inputs")).contains(&PathBuf::from("src/day_18/tree.rs")));
+    }
+
+    #[test]
+    fn configured_inputs_are_watched() {
+        let inputs: PathBuf = std::env::temp_dir().join("aoc_watched_inputs");
+        std::fs::create_dir_all(&inputs).unwrap();
+        std::fs::write(inputs.join("16.in"), "D2FE28").unwrap();
+        std::fs::write(inputs.join("18.in"), "[1,2]").unwrap();
+
+        let files = watched_files(16, &inputs);
+
+        assert!(files.contains(&inputs.join("16.in")));
+        assert!(!files.contains(&inputs.join("18.in")));
+    }
+}